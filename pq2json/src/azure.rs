@@ -0,0 +1,99 @@
+use std::env;
+use std::error::Error;
+
+use log::debug;
+use serde_json::Value;
+
+/// Azure Storage REST API version of the blob requests.
+const STORAGE_API_VERSION: &str = "2020-04-08";
+
+const STORAGE_SCOPE: &str = "https://storage.azure.com/.default";
+const STORAGE_RESOURCE: &str = "https://storage.azure.com/";
+const IMDS_TOKEN_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+/// Returns headers authorizing requests to a blob of Azure Blob Storage, none for other URLs
+/// and for blob URLs carrying a SAS token.
+///
+/// Azure Storage token is acquired (once per file) from the first available credential:
+///
+/// 1. Service principal with client secret, from `AZURE_TENANT_ID`, `AZURE_CLIENT_ID`
+///    and `AZURE_CLIENT_SECRET` environment variables
+/// 2. Managed identity, the user-assigned one selected by `AZURE_CLIENT_ID` if set
+pub(crate) fn blob_headers(url: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    if !is_blob_url(url) || has_sas_token(url) {
+        return Ok(Vec::new());
+    }
+    let token = match client_secret_token()? {
+        Some(token) => token,
+        None => managed_identity_token()?,
+    };
+    Ok(vec![
+        ("Authorization".to_owned(), format!("Bearer {}", token)),
+        ("x-ms-version".to_owned(), STORAGE_API_VERSION.to_owned()),
+    ])
+}
+
+fn is_blob_url(url: &str) -> bool {
+    let host = url
+        .splitn(2, "://")
+        .nth(1)
+        .and_then(|rest| rest.split(|c| c == '/' || c == '?').next())
+        .unwrap_or_default();
+    host.ends_with(".blob.core.windows.net")
+}
+
+fn has_sas_token(url: &str) -> bool {
+    url.splitn(2, '?').nth(1).map_or(false, |query| {
+        query.split('&').any(|param| param.starts_with("sig="))
+    })
+}
+
+fn client_secret_token() -> Result<Option<String>, Box<dyn Error>> {
+    let (tenant_id, client_id, client_secret) = match (
+        env::var("AZURE_TENANT_ID"),
+        env::var("AZURE_CLIENT_ID"),
+        env::var("AZURE_CLIENT_SECRET"),
+    ) {
+        (Ok(tenant_id), Ok(client_id), Ok(client_secret)) => (tenant_id, client_id, client_secret),
+        _ => return Ok(None),
+    };
+    debug!("Acquiring Azure Storage token for client {}", client_id);
+    let response = ureq::post(&format!(
+        "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+        tenant_id
+    ))
+    .send_form(&[
+        ("grant_type", "client_credentials"),
+        ("client_id", &client_id),
+        ("client_secret", &client_secret),
+        ("scope", STORAGE_SCOPE),
+    ])?;
+    access_token(&response.into_string()?).map(Some)
+}
+
+fn managed_identity_token() -> Result<String, Box<dyn Error>> {
+    debug!("Acquiring Azure Storage token for managed identity");
+    let mut request = ureq::get(IMDS_TOKEN_ENDPOINT)
+        .set("Metadata", "true")
+        .query("api-version", "2018-02-01")
+        .query("resource", STORAGE_RESOURCE);
+    if let Ok(client_id) = env::var("AZURE_CLIENT_ID") {
+        request = request.query("client_id", &client_id);
+    }
+    let response = request.call().map_err(|e| {
+        format!(
+            "No Azure credentials in the environment, and managed identity is not available: {}",
+            e
+        )
+    })?;
+    access_token(&response.into_string()?)
+}
+
+fn access_token(response: &str) -> Result<String, Box<dyn Error>> {
+    let response = serde_json::from_str::<Value>(response)?;
+    response
+        .get("access_token")
+        .and_then(|token| token.as_str())
+        .map(|token| token.to_owned())
+        .ok_or_else(|| "Token response doesn't contain access_token".into())
+}
@@ -1,359 +1,2029 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::error::Error;
-use std::fs::File;
-use std::io::{self, BufWriter, Write};
-use std::path::Path;
+use std::io::{self, Write};
+use std::sync::Arc;
 
 use num_bigint::{BigInt, Sign};
+use parquet::basic::LogicalType;
 use parquet::data_type::Decimal;
-use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::reader::{ChunkReader, FileReader, SerializedFileReader};
 use parquet::record::{FieldType, List, ListAccessor, Map, MapAccessor, Row, RowAccessor};
-use parquet::schema::types::Type as SchemaType;
+use parquet::schema::printer::print_schema;
+use parquet::schema::types::{Type as SchemaType, TypePtr};
+use serde_json::map::Entry;
 use serde_json::{Number, Value};
 
-use crate::settings::{Settings, TimestampRendering};
-use chrono::Duration;
-use csv::Terminator;
+use crate::error::{ColumnError, ConvertError, RowError};
+use crate::input::{check_schemas, open_input, select_row_groups, STDIN_INPUT};
+use crate::ipc::write_arrow_stream;
+use crate::output::{row_group_path, truncate_checksum_file, Output};
+use crate::parallel::parallel_records;
+use crate::progress::Progress;
+use crate::schema::write_ingestion_mapping;
+use crate::settings::{
+    BytesEncoding, CsvQuote, CsvTerminator, DuplicateKey, ExplodeEmpty, FloatSpecial, Newline,
+    OutputFormat, OverflowHandling, PreEpoch, RowFilter, Settings, TimestampRendering, Transform,
+};
+use chrono::{Duration, TimeZone};
+use csv::{QuoteStyle, Terminator};
+use itertools::Itertools;
+use log::{info, warn};
 use parquet::record::reader::RowIter;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-const WRITER_BUF_CAP: usize = 256 * 1024;
-
-/// Writes Parquet file as text, either as JSONL (every line contains single JSON record)
-/// or as CSV (where nested structures are formatted as JSON strings).
+/// Writes Parquet file in the output format selected by settings: JSONL (every line contains
+/// single JSON record), CSV or TSV (where nested structures are formatted as JSON strings),
+/// a single JSON array, or an Arrow IPC stream.
 ///
 /// Arguments:
 ///
 /// * `settings` - Converter settings
-/// * `input_file` - Parquet file path
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
 /// * `output_file` - Optional output file path (if not provided - output is written to STDOUT).
 ///
 pub fn convert(
     settings: &Settings,
     input_file: &str,
     output_file: Option<&str>,
+) -> Result<(), ConvertError> {
+    convert_files(settings, &[input_file], output_file)
+}
+
+/// Writes multiple Parquet files, same as `convert`, as a single continuous output
+/// (files are read in the given order). Schemas of all files must match the schema
+/// of the first file, unless `ignore_schema_mismatch` is set.
+///
+/// Output of files without rows is still well-formed: the CSV header (with --csv-header),
+/// an empty JSON array or an Arrow stream with just the schema.
+///
+/// Arguments:
+///
+/// * `settings` - Converter settings
+/// * `input_files` - Parquet file paths (`-` denotes STDIN)
+/// * `output_file` - Optional output file path (if not provided - output is written to STDOUT).
+///
+pub fn convert_files(
+    settings: &Settings,
+    input_files: &[&str],
+    output_file: Option<&str>,
+) -> Result<(), ConvertError> {
+    if input_files.is_empty() {
+        return Err(ConvertError::Other("No input files".into()));
+    }
+    let readers = input_files
+        .iter()
+        .map(|input_file| open_input(settings, input_file))
+        .collect::<Result<Vec<_>, _>>()?;
+    check_schemas(input_files, &readers, settings.ignore_schema_mismatch)?;
+    // Output files of every row group with --per-row-group share the checksum file.
+    truncate_checksum_file(settings)?;
+    if settings.per_row_group {
+        return Ok(convert_per_row_group(
+            settings,
+            input_files,
+            readers,
+            output_file,
+        )?);
+    }
+    let mut output = Output::file(settings, output_file)?;
+    if settings.threads > 1 {
+        let projection = Projection::new(settings, readers[0].as_ref())?;
+        explode_column_index(settings, &projection)?;
+        write_mapping(settings, &projection.fields)?;
+        let records = parallel_records(
+            settings,
+            input_files,
+            &readers,
+            projection.schema,
+            projection.fields,
+        )?;
+        json_records_to_output(settings, records, &mut output)?;
+        return Ok(output.finish()?);
+    }
+    Ok(convert_readers(settings, input_files, readers, output)?)
+}
+
+/// Writes every selected row group of the files to its own output file, named after
+/// the output path (`out.jsonl` -> `out.rg0.jsonl`, `out.rg1.jsonl`, ...) and numbered
+/// across the files. Row-level options (--where, --skip, --limit, ...) apply to every
+/// row group separately.
+fn convert_per_row_group(
+    settings: &Settings,
+    input_files: &[&str],
+    readers: Vec<Box<dyn FileReader>>,
+    output_file: Option<&str>,
 ) -> Result<(), Box<dyn Error>> {
-    let file = File::open(&Path::new(input_file))?;
-    let reader = SerializedFileReader::new(file)?;
-
-    let writer = match output_file {
-        Some(output_file) => Box::new(BufWriter::with_capacity(
-            WRITER_BUF_CAP,
-            File::create(&Path::new(output_file))?,
-        )) as Box<dyn Write>,
-        None => Box::new(BufWriter::with_capacity(WRITER_BUF_CAP, io::stdout())) as Box<dyn Write>,
-    };
+    let output_file = output_file.ok_or("--per-row-group requires an output file")?;
+    if input_files.contains(&STDIN_INPUT) {
+        return Err("--per-row-group requires input files, STDIN can't be reopened".into());
+    }
+    let unsupported = [
+        ("--threads", settings.threads > 1),
+        ("--split-rows", settings.split_rows.is_some()),
+        ("--split-bytes", settings.split_bytes.is_some()),
+        ("--resume-from", settings.resume_from > 0),
+    ];
+    if let Some((option, _)) = unsupported.iter().find(|(_, present)| *present) {
+        return Err(format!("{} is not supported with --per-row-group", option).into());
+    }
 
-    let mut missing_columns = std::collections::HashSet::new();
-    let schema = settings
-        .columns
-        .as_ref()
-        .map(|c| projected_schema(&reader, &c, &mut missing_columns).unwrap());
+    let mut index = 0;
+    for (input_file, reader) in input_files.iter().zip(readers) {
+        let row_groups = match settings.row_groups {
+            Some(ref indices) => indices.clone(),
+            None => (0..reader.num_row_groups()).collect(),
+        };
+        // Conversion consumes the reader, so the following row groups reopen the file.
+        let mut reader = Some(reader);
+        for row_group in row_groups {
+            let reader = match reader.take() {
+                Some(reader) => reader,
+                None => open_input(settings, input_file)?,
+            };
+            let row_group_settings = Settings {
+                row_groups: Some(vec![row_group]),
+                ..settings.clone()
+            };
+            let row_group_file = row_group_path(output_file, index);
+            info!(
+                "Writing row group {} of {} to {}",
+                row_group, input_file, row_group_file
+            );
+            let output = Output::file(&row_group_settings, Some(&row_group_file))?;
+            convert_readers(&row_group_settings, &[*input_file], vec![reader], output)?;
+            index += 1;
+        }
+    }
+    Ok(())
+}
 
-    let rows = reader.get_row_iter(schema)?;
+/// Converts Parquet files same as `convert_files`, but discards the output, printing
+/// the number of converted rows, or failing with the first conversion error
+/// (`RowError` with the file and the zero-based index of the row in it).
+///
+/// Arguments:
+///
+/// * `settings` - Converter settings
+/// * `input_files` - Parquet file paths (`-` denotes STDIN)
+///
+pub fn validate_files(settings: &Settings, input_files: &[&str]) -> Result<(), ConvertError> {
+    if input_files.is_empty() {
+        return Err(ConvertError::Other("No input files".into()));
+    }
+    let readers = input_files
+        .iter()
+        .map(|input_file| open_input(settings, input_file))
+        .collect::<Result<Vec<_>, _>>()?;
+    check_schemas(input_files, &readers, settings.ignore_schema_mismatch)?;
+    let projection = Projection::new(settings, readers[0].as_ref())?;
+    explode_column_index(settings, &projection)?;
 
-    if settings.csv {
-        top_level_rows_to_csv(&settings, rows, missing_columns, writer)
-    } else {
-        top_level_rows_to_json(&settings, rows, writer)
+    let mut rows_converted = 0usize;
+    let rows = top_level_rows(settings, readers, projection.schema)?;
+    for (file_idx, row_idx, row) in rows {
+        let input_file = input_files.get(file_idx).copied();
+        top_level_row_to_value(settings, &projection.fields, &row)
+            .map(|value| add_synthetic_columns(settings, value, input_file, row_idx))
+            .and_then(|value| explode_row(settings, value))
+            .and_then(|values| {
+                values
+                    .into_iter()
+                    .try_for_each(|value| value_to_json(settings, value).map(|_| ()))
+            })
+            .map_err(|e| RowError::new(input_file, row_idx, e))?;
+        rows_converted += 1;
     }
+    println!("{} rows converted successfully", rows_converted);
+    Ok(())
 }
 
-fn projected_schema(
-    reader: &SerializedFileReader<File>,
-    columns: &Vec<String>,
-    missing_columns: &mut std::collections::HashSet<std::string::String>,
-) -> Result<SchemaType, Box<dyn Error>> {
-    let file_meta = reader.metadata().file_metadata();
-    let mut schema_fields = HashMap::new();
-    for field in file_meta.schema().get_fields().iter() {
-        schema_fields.insert(field.name().to_owned(), field);
+/// Describes the planned conversion on STDERR and exits without converting: the input files,
+/// the schema, the selected columns, the output and the settings, and the number of rows
+/// estimated from the row groups metadata.
+///
+/// Arguments:
+///
+/// * `settings` - Converter settings
+/// * `input_files` - Parquet file paths (`-` denotes STDIN)
+/// * `output_file` - Optional output file path (if not provided - output is written to STDOUT).
+///
+pub fn explain_files(
+    settings: &Settings,
+    input_files: &[&str],
+    output_file: Option<&str>,
+) -> Result<(), ConvertError> {
+    if input_files.is_empty() {
+        return Err(ConvertError::Other("No input files".into()));
     }
+    let readers = input_files
+        .iter()
+        .map(|input_file| open_input(settings, input_file))
+        .collect::<Result<Vec<_>, _>>()?;
+    check_schemas(input_files, &readers, settings.ignore_schema_mismatch)?;
+    let projection = Projection::new(settings, readers[0].as_ref())?;
 
-    let mut projected_fields = Vec::new();
+    let mut selected_rows = 0usize;
+    for (input_file, reader) in input_files.iter().zip(readers.iter()) {
+        let row_groups = reader.metadata().row_groups();
+        let rows = row_groups
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| {
+                settings
+                    .row_groups
+                    .as_ref()
+                    .map_or(true, |selected| selected.contains(i))
+            })
+            .map(|(_, row_group_metadata)| row_group_metadata.num_rows() as usize)
+            .sum::<usize>();
+        eprintln!(
+            "Input: {} ({} rows selected, {} row groups)",
+            input_file,
+            rows,
+            row_groups.len()
+        );
+        selected_rows += rows;
+    }
 
-    for c in columns.iter() {
-        let res = schema_fields.get_mut(c);
+    let mut schema = Vec::new();
+    print_schema(&mut schema, readers[0].metadata().file_metadata().schema());
+    eprintln!("Schema:\n{}", String::from_utf8_lossy(&schema).trim_end());
+    eprintln!(
+        "Columns: {}",
+        projection
+            .fields
+            .iter()
+            .map(|field| column_alias(settings, field.name()))
+            .join(", ")
+    );
+    eprintln!(
+        "Output: {} ({:?})",
+        output_file.unwrap_or("STDOUT"),
+        settings.format
+    );
+    eprintln!("Settings: {:#?}", settings);
 
-        match res {
-            Some(ptr) => {
-                projected_fields.push(ptr.clone());
-            }
-            None => {
-                missing_columns.insert(c.clone());
-            }
+    let rows = match settings.tail {
+        Some(tail) => selected_rows.min(tail),
+        None => selected_rows.saturating_sub(settings.resume_from.saturating_add(settings.skip)),
+    };
+    let rows = settings.limit.map_or(rows, |limit| rows.min(limit));
+    let filtered = settings.filter.is_some()
+        || settings.sample.is_some()
+        || settings.skip_empty_rows
+        || settings.explode.is_some();
+    eprintln!(
+        "Estimated rows: {}{}",
+        rows,
+        if filtered {
+            " (before --where, --sample, --skip-empty-rows and --explode)"
+        } else {
+            ""
         }
+    );
+    Ok(())
+}
+
+/// Writes Parquet data, same as `convert`, but reads from and writes to
+/// caller-provided streams without touching the filesystem.
+///
+/// Arguments:
+///
+/// * `settings` - Converter settings
+/// * `input` - Parquet data source, e.g. `File` or in-memory `SliceableCursor`
+/// * `output` - Output stream
+///
+pub fn convert_from<R, W>(settings: &Settings, input: R, output: W) -> Result<(), ConvertError>
+where
+    R: ChunkReader + 'static,
+    W: Write + 'static,
+{
+    let reader = Box::new(SerializedFileReader::new(input)?);
+    let output = Output::stream(settings, Box::new(output))?;
+    Ok(convert_readers(settings, &[], vec![reader], output)?)
+}
+
+/// Returns an iterator over converted top-level rows, one JSON value per row,
+/// honoring row selection and projection settings. Conversion errors are `ConvertError::Row`.
+///
+/// Arguments:
+///
+/// * `settings` - Converter settings
+/// * `reader` - Parquet file reader, e.g. boxed `SerializedFileReader`
+///
+pub fn rows<'a>(
+    settings: &'a Settings,
+    reader: Box<dyn FileReader>,
+) -> Result<impl Iterator<Item = Result<Value, ConvertError>> + 'a, ConvertError> {
+    let values = readers_to_values(settings, &[], vec![reader])?;
+    Ok(values.map(|value| value.map_err(ConvertError::from)))
+}
+
+/// Converts top-level rows same as `rows`, calling `f` with every converted row, e.g. to write
+/// records to a custom sink. Stops at the first conversion error or error returned by `f`.
+///
+/// Arguments:
+///
+/// * `settings` - Converter settings
+/// * `reader` - Parquet file reader, e.g. boxed `SerializedFileReader`
+/// * `f` - Function called with every row
+///
+pub fn for_each_row<F>(
+    settings: &Settings,
+    reader: Box<dyn FileReader>,
+    mut f: F,
+) -> Result<(), ConvertError>
+where
+    F: FnMut(&Value) -> Result<(), Box<dyn Error>>,
+{
+    for value in readers_to_values(settings, &[], vec![reader])? {
+        f(&value?)?;
     }
+    Ok(())
+}
 
-    Ok(
-        SchemaType::group_type_builder(&file_meta.schema().get_basic_info().name())
-            .with_fields(&mut projected_fields)
-            .build()
-            .unwrap(),
-    )
+/// Converted top-level rows of the readers, `input_files` being their paths
+/// (empty for caller-provided readers).
+fn readers_to_values<'a>(
+    settings: &'a Settings,
+    input_files: &[&str],
+    readers: Vec<Box<dyn FileReader>>,
+) -> Result<impl Iterator<Item = Result<Value, Box<dyn Error>>> + 'a, Box<dyn Error>> {
+    let projection = Projection::new(settings, readers[0].as_ref())?;
+    projected_values(settings, input_files, readers, projection)
 }
 
-macro_rules! element_to_value {
-    ($ft:expr, $obj:ident, $i:ident, $settings:ident) => {
-        match $ft {
-            FieldType::Null => Value::Null,
-            FieldType::Bool => Value::Bool($obj.get_bool($i)?),
-            FieldType::Byte => Value::Number($obj.get_byte($i)?.into()),
-            FieldType::Short => Value::Number($obj.get_short($i)?.into()),
-            FieldType::Int => Value::Number($obj.get_int($i)?.into()),
-            FieldType::Long => Value::Number($obj.get_long($i)?.into()),
-            FieldType::UByte => Value::Number($obj.get_ubyte($i)?.into()),
-            FieldType::UShort => Value::Number($obj.get_ushort($i)?.into()),
-            FieldType::UInt => Value::Number($obj.get_uint($i)?.into()),
-            FieldType::ULong => ulong_to_value($obj.get_ulong($i)?, &$settings),
-            FieldType::Float => float_to_value($obj.get_float($i)? as f64),
-            FieldType::Double => float_to_value($obj.get_double($i)?),
-            FieldType::Decimal => Value::String(decimal_to_string($obj.get_decimal($i)?)),
-            FieldType::Str => Value::String($obj.get_string($i)?.to_string()),
-            FieldType::Bytes => bytes_to_value($obj.get_bytes($i)?.data()),
-            FieldType::Date => date_to_value($obj.get_date($i)?)?,
-            FieldType::TimestampMillis => {
-                timestamp_to_value($settings, $obj.get_timestamp_millis($i)?)?
-            }
-            FieldType::TimestampMicros => timestamp_to_value(
-                $settings,
-                $obj.get_timestamp_micros($i).map(|ts| ts / 1000)?,
-            )?,
-            FieldType::Group => row_to_value($settings, $obj.get_group($i)?)?,
-            FieldType::List => list_to_value($settings, $obj.get_list($i)?)?,
-            FieldType::Map => map_to_value($settings, $obj.get_map($i)?)?,
+fn projected_values<'a>(
+    settings: &'a Settings,
+    input_files: &[&str],
+    readers: Vec<Box<dyn FileReader>>,
+    projection: Projection,
+) -> Result<impl Iterator<Item = Result<Value, Box<dyn Error>>> + 'a, Box<dyn Error>> {
+    explode_column_index(settings, &projection)?;
+    let fields = projection.fields;
+    let input_files = input_files
+        .iter()
+        .map(|input_file| input_file.to_string())
+        .collect::<Vec<_>>();
+    let rows = top_level_rows(settings, readers, projection.schema)?;
+    Ok(rows.flat_map(move |(file_idx, row_idx, row)| {
+        let input_file = input_files
+            .get(file_idx)
+            .map(|input_file| input_file.as_str());
+        match top_level_row_to_value(settings, &fields, &row)
+            .map(|value| add_synthetic_columns(settings, value, input_file, row_idx))
+            .and_then(|value| explode_row(settings, value))
+        {
+            Ok(values) => values
+                .into_iter()
+                .filter(|value| !is_skipped_empty_row(settings, value))
+                .map(Ok)
+                .collect::<Vec<_>>(),
+            Err(e) => vec![Err(RowError::new(input_file, row_idx, e).into())],
         }
-    };
+    }))
 }
 
-fn top_level_rows_to_json(
+/// Converts rows of the readers (non-empty, in order) into a single output,
+/// projection is resolved against the first reader.
+fn convert_readers(
     settings: &Settings,
-    mut rows: RowIter,
-    mut writer: Box<dyn Write>,
+    input_files: &[&str],
+    readers: Vec<Box<dyn FileReader>>,
+    mut output: Output,
 ) -> Result<(), Box<dyn Error>> {
-    while let Some(row) = rows.next() {
-        let value = row_to_value(settings, &row)?;
-        let value = if value.is_null() {
-            Value::Object(serde_json::Map::default())
-        } else {
-            value
-        };
-        writeln!(writer, "{}", serde_json::to_string(&value)?)?;
+    if settings.format == OutputFormat::Arrow {
+        check_mapping_format(settings)?;
+        write_arrow_stream(settings, readers, output.next_writer()?)?;
+        return output.finish();
+    }
+    let projection = Projection::new(settings, readers[0].as_ref())?;
+    write_mapping(settings, &projection.fields)?;
+    match settings.format {
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            convert_readers_to_csv(settings, input_files, readers, projection, &mut output)?
+        }
+        _ => top_level_rows_to_json(
+            &settings,
+            projected_values(settings, input_files, readers, projection)?,
+            &mut output,
+        )?,
+    }
+    output.finish()
+}
+
+/// Writes Kusto JSON ingestion mapping of the converted columns to --mapping-out, if specified.
+fn write_mapping(settings: &Settings, fields: &[TypePtr]) -> Result<(), Box<dyn Error>> {
+    check_mapping_format(settings)?;
+    match settings.mapping_out {
+        Some(ref mapping_file) => write_ingestion_mapping(settings, fields, mapping_file),
+        None => Ok(()),
+    }
+}
+
+fn check_mapping_format(settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let json_format = matches!(
+        settings.format,
+        OutputFormat::Jsonl | OutputFormat::JsonArray | OutputFormat::KustoMultiJson
+    );
+    if settings.mapping_out.is_some() && !json_format {
+        return Err("--mapping-out requires a JSON output format".into());
     }
     Ok(())
 }
 
-fn top_level_rows_to_csv(
+fn convert_readers_to_csv(
     settings: &Settings,
-    mut rows: RowIter,
-    missing_columns: std::collections::HashSet<std::string::String>,
-    mut writer: Box<dyn Write>,
+    input_files: &[&str],
+    readers: Vec<Box<dyn FileReader>>,
+    projection: Projection,
+    output: &mut Output,
 ) -> Result<(), Box<dyn Error>> {
-    while let Some(row) = rows.next() {
-        let mut csv_writer = csv::WriterBuilder::new()
-            .terminator(Terminator::Any(b'\r'))
-            .from_writer(vec![]);
-        let mut column_idx = 0;
-        let columns = settings.columns.as_ref();
-
-        match columns {
-            Some(cols) => {
-                // Produce empty values for columns specified by --columns argument, but missing in the file
-                for col in cols {
-                    let value = if missing_columns.contains(col) {
-                        Value::Null
-                    } else {
-                        let field_type = row.get_field_type(column_idx);
-                        let val = element_to_value!(field_type, row, column_idx, settings);
-                        column_idx += 1;
-                        val
-                    };
-
-                    csv_writer.write_field(value_to_csv(&value))?;
-                }
-            }
-            None => {
-                // No columns specified by --columns argument
-                for i in 0..row.len() {
-                    let field_type = row.get_field_type(i);
-                    let value = element_to_value!(field_type, row, i, settings);
-                    csv_writer.write_field(value_to_csv(&value))?;
-                }
-            }
-        };
+    let flattened = flattened_fields(settings, &projection.fields);
+    let explode_idx = explode_column_index(settings, &projection)?;
+    let header = if settings.csv_header {
+        Some(csv_header(settings, &projection, &flattened))
+    } else {
+        None
+    };
+    let rows = top_level_rows(settings, readers, projection.schema.clone())?
+        .map(|(file_idx, row_idx, row)| (input_files.get(file_idx).copied(), row_idx, row));
+    top_level_rows_to_csv(
+        &settings,
+        rows,
+        &projection,
+        flattened,
+        explode_idx,
+        header,
+        output,
+    )
+}
 
-        csv_writer.write_record(None::<&[u8]>)?;
-        writeln!(writer, "{}", String::from_utf8(csv_writer.into_inner()?)?)?;
-    }
-    Ok(())
+/// Projection of the file schema defined by --columns or --exclude-columns.
+struct Projection {
+    /// Projected schema (`None` to read all columns).
+    schema: Option<SchemaType>,
+    /// Output top-level columns, including requested columns missing in the file.
+    columns: Option<Vec<String>>,
+    missing_columns: HashSet<String>,
+    /// Top-level fields of the rows read, in row order.
+    fields: Vec<TypePtr>,
 }
 
-fn value_to_csv(value: &Value) -> String {
-    match value {
-        Value::Null => String::new(),
-        Value::Bool(v) => v.to_string(),
-        Value::Number(ref v) => {
-            if v.is_f64() {
-                let mut buffer = ryu::Buffer::new();
-                truncate_trailing_zeros(buffer.format(v.as_f64().unwrap())).to_string()
-            } else if v.is_u64() {
-                format!("{}", v.as_u64().unwrap())
-            } else {
-                format!("{}", v.as_i64().unwrap())
+impl Projection {
+    fn new(settings: &Settings, reader: &dyn FileReader) -> Result<Self, Box<dyn Error>> {
+        let columns = match settings.exclude_columns {
+            Some(ref exclude_columns) => Some(kept_columns(reader, exclude_columns)),
+            None => selected_columns(settings, reader),
+        };
+
+        let filter_column = match columns {
+            Some(ref c) => unselected_filter_column(settings, reader, c),
+            None => None,
+        };
+
+        let mut missing_columns = HashSet::new();
+        let mut output_columns = Vec::new();
+        let schema = match columns {
+            Some(ref c) => {
+                let mut read_columns = c.clone();
+                read_columns.extend(filter_column.clone());
+                Some(projected_schema(
+                    reader,
+                    &read_columns,
+                    &mut missing_columns,
+                    &mut output_columns,
+                )?)
             }
+            None => None,
+        };
+        if filter_column.is_some() {
+            // Read last, only to evaluate --where.
+            output_columns.pop();
         }
-        Value::String(ref v) => v.to_owned(),
-        Value::Array(ref v) => serde_json::to_string(&v).unwrap(),
-        Value::Object(ref v) => serde_json::to_string(&v).unwrap(),
+        if !missing_columns.is_empty() {
+            let missing = output_columns
+                .iter()
+                .filter(|column| missing_columns.contains(*column))
+                .join(", ");
+            if settings.strict_columns {
+                return Err(ConvertError::MissingColumn(missing).into());
+            }
+            warn!("Columns not found in the file: {}", missing);
+        }
+
+        let fields = match schema {
+            Some(ref schema) => {
+                let fields = schema.get_fields();
+                fields[..fields.len() - filter_column.iter().count()].to_vec()
+            }
+            None => reader
+                .metadata()
+                .file_metadata()
+                .schema()
+                .get_fields()
+                .to_vec(),
+        };
+
+        Ok(Projection {
+            schema,
+            fields,
+            columns: columns.map(|_| output_columns),
+            missing_columns,
+        })
     }
 }
 
-fn truncate_trailing_zeros(str: &str) -> &str {
-    str.trim_end_matches('0').trim_end_matches('.')
-}
+/// Reads top-level rows of the readers one after another, each with the index of its reader
+/// and its index in the file (counting every row of the file, regardless of row group
+/// selection, skipping and filtering). Row group selection applies to every file,
+/// filtering, sampling, --skip and --limit apply to the whole stream.
+fn top_level_rows(
+    settings: &Settings,
+    readers: Vec<Box<dyn FileReader>>,
+    schema: Option<SchemaType>,
+) -> Result<Box<dyn Iterator<Item = (usize, usize, Row)>>, Box<dyn Error>> {
+    // Readers of the selected row groups, each with the numbers of rows of its row groups
+    // and their ranges of row indices in the file.
+    let mut selected = Vec::with_capacity(readers.len());
+    for reader in readers {
+        info!(
+            "{} row groups, {} rows",
+            reader.num_row_groups(),
+            reader.metadata().file_metadata().num_rows()
+        );
+        let indices = match settings.row_groups {
+            Some(ref indices) => indices.clone(),
+            None => (0..reader.num_row_groups()).collect(),
+        };
+        let row_group_starts = reader
+            .metadata()
+            .row_groups()
+            .iter()
+            .scan(0, |start, row_group| {
+                let row_group_start = *start;
+                *start += row_group.num_rows() as usize;
+                Some(row_group_start)
+            })
+            .collect::<Vec<_>>();
+        let reader = select_row_groups(reader, &indices)?;
+        let row_ranges = indices
+            .iter()
+            .map(|&i| {
+                let start = row_group_starts[i];
+                start..start + reader.metadata().row_group(i).num_rows() as usize
+            })
+            .collect::<Vec<_>>();
+        let row_counts = row_ranges
+            .iter()
+            .map(|range| range.len())
+            .collect::<Vec<_>>();
+        selected.push((reader, row_counts, row_ranges));
+    }
+    let selected_rows = selected
+        .iter()
+        .flat_map(|(_, row_counts, _)| row_counts)
+        .sum::<usize>();
 
-fn row_to_value(settings: &Settings, row: &Row) -> Result<Value, Box<dyn Error>> {
-    let mut map = serde_json::Map::with_capacity(row.len());
-    for i in 0..row.len() {
-        let name = row.get_field_name(i);
-        let field_type = row.get_field_type(i);
-        let value = element_to_value!(field_type, row, i, settings);
-        if !(settings.omit_nulls && value.is_null()) {
-            map.insert(name.to_string(), value);
+    // Rows before --tail or --resume-from are skipped by whole row groups where possible,
+    // the remaining ones (`resumed`) before filtering. --skip counts matching rows instead.
+    let file_row_counts = selected
+        .iter()
+        .map(|(_, row_counts, _)| row_counts.as_slice())
+        .collect::<Vec<_>>();
+    let ((skipped_groups, resumed), skip) = match settings.tail {
+        Some(tail) => {
+            check_tail_settings(settings)?;
+            (
+                skipped_row_groups(&file_row_counts, selected_rows.saturating_sub(tail)),
+                0,
+            )
         }
-    }
+        None => (
+            skipped_row_groups(&file_row_counts, settings.resume_from),
+            settings.skip,
+        ),
+    };
 
-    if settings.omit_empty_bags && map.is_empty() {
-        Ok(Value::Null)
-    } else {
-        Ok(Value::Object(map))
+    let mut file_rows = Vec::with_capacity(selected.len());
+    for ((reader, row_counts, row_ranges), skipped) in selected.into_iter().zip(skipped_groups) {
+        let reader = if skipped > 0 {
+            select_row_groups(reader, &(skipped..row_counts.len()).collect::<Vec<_>>())?
+        } else {
+            reader
+        };
+        let rows = RowIter::from_file_into(reader).project(schema.clone())?;
+        let row_indices = row_ranges.into_iter().skip(skipped).flatten();
+        file_rows.push(rows.zip(row_indices));
     }
-}
 
-fn list_to_value(settings: &Settings, list: &List) -> Result<Value, Box<dyn Error>> {
-    let mut arr = Vec::<Value>::with_capacity(list.len());
-    for i in 0..list.len() {
-        let elt_ty = list.get_element_type(i);
-        let value = element_to_value!(elt_ty, list, i, settings);
-        arr.push(value);
-    }
+    let filter = settings.filter.clone();
+    let sample = settings.sample;
+    let mut rng = match settings.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let rows = file_rows
+        .into_iter()
+        .enumerate()
+        .flat_map(|(file_idx, rows)| rows.map(move |(row, row_idx)| (file_idx, row_idx, row)))
+        .skip(resumed)
+        .filter(move |(_, _, row)| {
+            filter
+                .as_ref()
+                .map_or(true, |filter| row_matches(filter, row))
+        })
+        .filter(move |_| sample.map_or(true, |rate| rng.gen::<f64>() < rate))
+        .skip(skip)
+        .take(settings.limit.unwrap_or(usize::MAX));
 
-    if settings.omit_empty_lists && arr.is_empty() {
-        Ok(Value::Null)
+    if settings.progress {
+        // Number of matching rows is not known upfront.
+        let total_rows = match (&settings.filter, settings.sample) {
+            (Some(_), _) | (_, Some(_)) => None,
+            (None, None) => Some(
+                settings
+                    .tail
+                    .unwrap_or(usize::MAX)
+                    .min(
+                        selected_rows
+                            .saturating_sub(settings.resume_from.saturating_add(settings.skip)),
+                    )
+                    .min(settings.limit.unwrap_or(usize::MAX)),
+            ),
+        };
+        Ok(Box::new(Progress::new(rows, total_rows)))
     } else {
-        Ok(Value::Array(arr))
+        Ok(Box::new(rows))
     }
 }
 
-fn map_to_value(settings: &Settings, map: &Map) -> Result<Value, Box<dyn Error>> {
-    let mut jsmap = serde_json::Map::with_capacity(map.len());
-    let keys = map.get_keys();
-    let values = map.get_values();
-    for i in 0..map.len() {
-        let key_ty = keys.get_element_type(i);
-        let key = match key_ty {
-            FieldType::Null => String::from("null"),
-            FieldType::Bool => keys.get_bool(i)?.to_string(),
-            FieldType::Byte => keys.get_byte(i)?.to_string(),
-            FieldType::Short => keys.get_short(i)?.to_string(),
-            FieldType::Int => keys.get_int(i)?.to_string(),
-            FieldType::Long => keys.get_long(i)?.to_string(),
-            FieldType::UByte => keys.get_ubyte(i)?.to_string(),
-            FieldType::UShort => keys.get_ushort(i)?.to_string(),
-            FieldType::UInt => keys.get_uint(i)?.to_string(),
-            FieldType::ULong => keys.get_ulong(i)?.to_string(),
-            FieldType::Str => keys.get_string(i)?.to_string(),
-            // TODO: return error here
-            _ => panic!("Unsupported map key"),
-        };
+/// Evaluates --where filter against a row. Rows where the column is missing, null,
+/// or not comparable with the filter value (e.g. non-scalar) don't match.
+pub(crate) fn row_matches(filter: &RowFilter, row: &Row) -> bool {
+    let i = match (0..row.len()).find(|&i| row.get_field_name(i) == filter.column) {
+        Some(i) => i,
+        None => return false,
+    };
 
-        let val_ty = values.get_element_type(i);
-        let value = element_to_value!(val_ty, values, i, settings);
-        if !(settings.omit_nulls && value.is_null()) {
-            jsmap.insert(key, value);
+    let number = match row.get_field_type(i) {
+        FieldType::Byte => row.get_byte(i).ok().map(f64::from),
+        FieldType::Short => row.get_short(i).ok().map(f64::from),
+        FieldType::Int => row.get_int(i).ok().map(f64::from),
+        FieldType::Long => row.get_long(i).ok().map(|v| v as f64),
+        FieldType::UByte => row.get_ubyte(i).ok().map(f64::from),
+        FieldType::UShort => row.get_ushort(i).ok().map(f64::from),
+        FieldType::UInt => row.get_uint(i).ok().map(f64::from),
+        FieldType::ULong => row.get_ulong(i).ok().map(|v| v as f64),
+        FieldType::Float => row.get_float(i).ok().map(f64::from),
+        FieldType::Double => row.get_double(i).ok(),
+        FieldType::Str => {
+            return match row.get_string(i) {
+                Ok(s) => filter.op.matches(s.as_str().cmp(filter.value.as_str())),
+                Err(_) => false,
+            };
         }
-    }
+        FieldType::Bool => {
+            return match row.get_bool(i) {
+                Ok(b) => filter.op.matches(b.to_string().cmp(&filter.value)),
+                Err(_) => false,
+            };
+        }
+        _ => None,
+    };
 
-    if settings.omit_empty_bags && jsmap.is_empty() {
-        Ok(Value::Null)
-    } else {
-        Ok(Value::Object(jsmap))
+    match (number, filter.value.parse::<f64>()) {
+        (Some(number), Ok(value)) => number
+            .partial_cmp(&value)
+            .map_or(false, |ordering| filter.op.matches(ordering)),
+        _ => false,
     }
 }
 
-fn bytes_to_value(bytes: &[u8]) -> Value {
-    let nums = bytes
-        .iter()
-        .map(|&b| Value::Number(b.into()))
-        .collect::<Vec<_>>();
-    Value::Array(nums)
-}
+/// CSV header follows the same column ordering as the data rows: either the projected
+/// --columns list (including columns missing in the file) or the file schema.
+/// With --flatten, struct columns are expanded into their dotted leaf paths.
+fn csv_header(
+    settings: &Settings,
+    projection: &Projection,
+    flattened: &[Option<Vec<String>>],
+) -> Vec<String> {
+    let columns = match projection.columns {
+        Some(ref columns) => columns.clone(),
+        None => projection
+            .fields
+            .iter()
+            .map(|field| field.name().to_owned())
+            .collect(),
+    };
 
-fn float_to_value(f: f64) -> Value {
-    Number::from_f64(f)
-        .map(|n| Value::Number(n))
-        .unwrap_or_else(|| Value::Null)
+    let mut header = Vec::with_capacity(columns.len() + 1);
+    let mut field_idx = 0;
+    for column in columns {
+        let name = column_alias(settings, &column).to_owned();
+        if projection.missing_columns.contains(&column) {
+            header.push(name);
+            continue;
+        }
+        match flattened[field_idx] {
+            Some(ref leaves) => header.extend(
+                leaves
+                    .iter()
+                    .map(|leaf| format!("{}{}{}", name, settings.flatten_separator, leaf)),
+            ),
+            None => header.push(name),
+        }
+        field_idx += 1;
+    }
+    header.extend(
+        synthetic_columns(settings, None, 0)
+            .into_iter()
+            .map(|(name, _)| name.to_owned()),
+    );
+    header
 }
 
-fn ulong_to_value(l: u64, settings: &Settings) -> Value {
-    if settings.convert_types {
-        Value::Number((l as i64).into())
-    } else {
-        Value::Number(l.into())
+/// --tail window is computed from the row counts in the metadata, so rows can't be
+/// filtered or skipped before it.
+fn check_tail_settings(settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let unsupported = [
+        ("--where", settings.filter.is_some()),
+        ("--sample", settings.sample.is_some()),
+        ("--skip", settings.skip > 0),
+        ("--resume-from", settings.resume_from > 0),
+    ];
+    match unsupported.iter().find(|(_, present)| *present) {
+        Some((option, _)) => Err(format!("{} is not supported with --tail", option).into()),
+        None => Ok(()),
     }
 }
 
-const TICKS_TILL_UNIX_TIME: u64 = 621355968000000000u64;
-
-fn timestamp_to_value(settings: &Settings, ts: u64) -> Result<Value, Box<dyn Error>> {
-    match settings.timestamp_rendering {
-        TimestampRendering::Ticks => {
-            let ticks = ts
-                .checked_mul(10000)
-                .and_then(|t| t.checked_add(TICKS_TILL_UNIX_TIME));
-            let v = ticks
-                .map(|t| Value::Number(t.into()))
-                .unwrap_or(Value::Null);
-            Ok(v)
+/// Counts leading row groups of every file (given by their row counts) whose rows are
+/// all within the first `skip` rows, so that they're not read at all. Returns the counts
+/// and the number of rows still to skip.
+fn skipped_row_groups(file_row_counts: &[&[usize]], mut skip: usize) -> (Vec<usize>, usize) {
+    let mut skipped_groups = vec![0; file_row_counts.len()];
+    for (skipped, row_counts) in skipped_groups.iter_mut().zip(file_row_counts) {
+        while *skipped < row_counts.len() && row_counts[*skipped] <= skip {
+            skip -= row_counts[*skipped];
+            *skipped += 1;
         }
-        TimestampRendering::IsoStr => {
-            let seconds = (ts / 1000) as i64;
-            let nanos = ((ts % 1000) * 1000000) as u32;
-            let datetime =
-                if let Some(dt) = chrono::NaiveDateTime::from_timestamp_opt(seconds, nanos) {
-                    dt
-                } else {
-                    return Ok(Value::Null);
-                };
-            let iso_str = datetime.format("%Y-%m-%dT%H:%M:%S.%6fZ").to_string();
-            Ok(Value::String(iso_str))
+        if *skipped < row_counts.len() {
+            break;
         }
-        TimestampRendering::UnixMs => Ok(Value::Number(ts.into())),
     }
+    (skipped_groups, skip)
 }
 
-fn date_to_value(days_from_epoch: u32) -> Result<Value, Box<dyn Error>> {
-    let date = match chrono::NaiveDate::from_ymd(1970, 1, 1)
-        .checked_add_signed(Duration::days(days_from_epoch as i64))
-    {
-        Some(date) => date,
-        None => return Ok(Value::Null),
+/// Returns position of the --explode column among the output columns,
+/// failing if the column is not read from the file.
+fn explode_column_index(
+    settings: &Settings,
+    projection: &Projection,
+) -> Result<Option<usize>, Box<dyn Error>> {
+    let column = match settings.explode {
+        Some(ref column) => column,
+        None => return Ok(None),
     };
-    let iso_str = date.format("%Y-%m-%d").to_string();
-    Ok(Value::String(iso_str))
+    if !projection.fields.iter().any(|field| field.name() == column) {
+        return Err(
+            ConvertError::Schema(format!("Column to explode not found: {}", column)).into(),
+        );
+    }
+    let idx = match projection.columns {
+        Some(ref columns) => columns.iter().position(|c| c == column),
+        None => projection
+            .fields
+            .iter()
+            .position(|field| field.name() == column),
+    };
+    Ok(idx)
 }
 
-fn decimal_to_string(decimal: &Decimal) -> String {
-    assert!(decimal.scale() >= 0 && decimal.precision() > decimal.scale());
+/// Expands a converted top-level row into one row per element of the --explode list column,
+/// other columns being repeated.
+pub(crate) fn explode_row(settings: &Settings, value: Value) -> Result<Vec<Value>, Box<dyn Error>> {
+    let (column, keep_null) = match settings.explode {
+        Some(ref column) => (
+            column_alias(settings, column),
+            keeps_nulls(settings, column),
+        ),
+        None => return Ok(vec![value]),
+    };
+    let object = match value {
+        Value::Object(object) => object,
+        // Row omitted as an empty bag.
+        _ => serde_json::Map::default(),
+    };
+    let list = object.get(column).cloned().unwrap_or(Value::Null);
 
-    // Specify as signed bytes to resolve sign as part of conversion.
-    let num = BigInt::from_signed_bytes_be(decimal.data());
+    let mut rows = Vec::new();
+    for element in exploded_values(settings, list)? {
+        let mut row = object.clone();
+        if settings.omit_nulls && element.is_null() && !keep_null {
+            // Unlike `remove`, keeps the order of the other keys.
+            row.retain(|key, _| key != column);
+        } else {
+            row.insert(column.to_owned(), element);
+        }
+        rows.push(Value::Object(row));
+    }
+    Ok(rows)
+}
 
-    // Offset of the first digit in a string.
-    let negative = if num.sign() == Sign::Minus { 1 } else { 0 };
-    let mut num_str = num.to_string();
-    let mut point = num_str.len() as i32 - decimal.scale() - negative;
+/// Elements of an exploded list. Null (or omitted) lists are treated as empty lists,
+/// which produce either no rows or a single null element (see --explode-empty).
+fn exploded_values(settings: &Settings, list: Value) -> Result<Vec<Value>, Box<dyn Error>> {
+    match list {
+        Value::Array(elements) if !elements.is_empty() => Ok(elements),
+        Value::Array(_) | Value::Null => match settings.explode_empty {
+            ExplodeEmpty::Drop => Ok(Vec::new()),
+            ExplodeEmpty::Null => Ok(vec![Value::Null]),
+        },
+        _ => Err(ConvertError::Schema(format!(
+            "Column to explode is not a list: {}",
+            settings.explode.as_deref().unwrap_or_default()
+        ))
+        .into()),
+    }
+}
 
-    // Convert to string form without scientific notation.
+/// With --flatten, returns for every top-level field the dotted paths (relative to the field)
+/// of the struct leaves it expands into, so that all CSV rows have the same columns.
+/// Fields that are not structs, or all fields without --flatten, map to `None`.
+fn flattened_fields(settings: &Settings, fields: &[TypePtr]) -> Vec<Option<Vec<String>>> {
+    fields
+        .iter()
+        .map(|field| {
+            if settings.flatten && is_struct(field) {
+                Some(struct_leaves(field, &settings.flatten_separator))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn struct_leaves(field: &SchemaType, separator: &str) -> Vec<String> {
+    let mut leaves = Vec::new();
+    for child in field.get_fields() {
+        if is_struct(child) {
+            for leaf in struct_leaves(child, separator) {
+                leaves.push(format!("{}{}{}", child.name(), separator, leaf));
+            }
+        } else {
+            leaves.push(child.name().to_owned());
+        }
+    }
+    leaves
+}
+
+/// Plain structs are groups without LIST or MAP annotation.
+fn is_struct(field: &SchemaType) -> bool {
+    field.is_group() && field.get_basic_info().logical_type() == LogicalType::NONE
+}
+
+/// Returns the top-level --where column if it's in the file but not among the selected
+/// `columns`, so that it's read (after them) but not output.
+fn unselected_filter_column(
+    settings: &Settings,
+    reader: &dyn FileReader,
+    columns: &[String],
+) -> Option<String> {
+    let column = &settings.filter.as_ref()?.column;
+    let selected = columns
+        .iter()
+        .any(|c| c == column || c.starts_with(&format!("{}.", column)));
+    let in_file = reader
+        .metadata()
+        .file_metadata()
+        .schema()
+        .get_fields()
+        .iter()
+        .any(|field| field.name() == column);
+    if selected || !in_file {
+        None
+    } else {
+        Some(column.clone())
+    }
+}
+
+/// Columns selected by --columns, followed by the top-level columns matching
+/// --columns-regex (in file order), or `None` to read all columns.
+fn selected_columns(settings: &Settings, reader: &dyn FileReader) -> Option<Vec<String>> {
+    let regex = match settings.columns_regex {
+        Some(ref regex) => regex,
+        None => return settings.columns.clone(),
+    };
+    let mut columns = settings.columns.clone().unwrap_or_default();
+    let matching = reader
+        .metadata()
+        .file_metadata()
+        .schema()
+        .get_fields()
+        .iter()
+        .map(|field| field.name().to_owned())
+        .filter(|name| regex.is_match(name) && !columns.contains(name))
+        .collect_vec();
+    if matching.is_empty() {
+        warn!("No columns match --columns-regex {}", regex);
+    }
+    columns.extend(matching);
+    Some(columns)
+}
+
+fn kept_columns(reader: &dyn FileReader, exclude_columns: &[String]) -> Vec<String> {
+    reader
+        .metadata()
+        .file_metadata()
+        .schema()
+        .get_fields()
+        .iter()
+        .map(|field| field.name().to_owned())
+        .filter(|name| !exclude_columns.contains(name))
+        .collect()
+}
+
+fn projected_schema(
+    reader: &dyn FileReader,
+    columns: &Vec<String>,
+    missing_columns: &mut HashSet<String>,
+    output_columns: &mut Vec<String>,
+) -> Result<SchemaType, Box<dyn Error>> {
+    let file_meta = reader.metadata().file_metadata();
+    let schema_fields = file_meta.schema().get_fields();
+
+    // Nested paths are grouped by their top-level field, in order of first appearance.
+    let mut projections: Vec<(&TypePtr, Vec<Vec<&str>>)> = Vec::new();
+
+    for c in columns.iter() {
+        match resolve_column(schema_fields, c) {
+            Some((field, path)) => {
+                match projections
+                    .iter_mut()
+                    .find(|(projected, _)| projected.name() == field.name())
+                {
+                    Some((_, paths)) => paths.push(path),
+                    None => {
+                        output_columns.push(field.name().to_owned());
+                        projections.push((field, vec![path]));
+                    }
+                }
+            }
+            None => {
+                missing_columns.insert(c.clone());
+                output_columns.push(c.clone());
+            }
+        }
+    }
+
+    let mut projected_fields = projections
+        .into_iter()
+        .map(|(field, paths)| prune_field(field, paths))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(
+        SchemaType::group_type_builder(&file_meta.schema().get_basic_info().name())
+            .with_fields(&mut projected_fields)
+            .build()
+            .unwrap(),
+    )
+}
+
+/// Resolves column name (either top-level, or dotted path into nested structs)
+/// into its top-level field and the remaining nested path.
+fn resolve_column<'a>(
+    fields: &'a [TypePtr],
+    column: &'a str,
+) -> Option<(&'a TypePtr, Vec<&'a str>)> {
+    if let Some(field) = fields.iter().find(|field| field.name() == column) {
+        return Some((field, Vec::new()));
+    }
+
+    let path = column.split('.').collect::<Vec<_>>();
+    let top_level = fields.iter().find(|field| field.name() == path[0])?;
+    let mut current = top_level;
+    for name in &path[1..] {
+        // Only plain structs can be pruned, lists and maps are selected as a whole.
+        if !is_struct(current) {
+            return None;
+        }
+        current = current
+            .get_fields()
+            .iter()
+            .find(|field| field.name() == *name)?;
+    }
+    Some((top_level, path[1..].to_vec()))
+}
+
+fn prune_field(field: &TypePtr, paths: Vec<Vec<&str>>) -> Result<TypePtr, Box<dyn Error>> {
+    if paths.iter().any(|path| path.is_empty()) {
+        return Ok(field.clone());
+    }
+
+    let mut children: Vec<(&str, Vec<Vec<&str>>)> = Vec::new();
+    for path in paths {
+        let (name, rest) = (path[0], path[1..].to_vec());
+        match children.iter_mut().find(|(child, _)| *child == name) {
+            Some((_, child_paths)) => child_paths.push(rest),
+            None => children.push((name, vec![rest])),
+        }
+    }
+
+    let mut fields = Vec::with_capacity(children.len());
+    for (name, child_paths) in children {
+        let child = field
+            .get_fields()
+            .iter()
+            .find(|child| child.name() == name)
+            .expect("nested path must be resolved");
+        fields.push(prune_field(child, child_paths)?);
+    }
+
+    let basic_info = field.get_basic_info();
+    Ok(Arc::new(
+        SchemaType::group_type_builder(basic_info.name())
+            .with_repetition(basic_info.repetition())
+            .with_logical_type(basic_info.logical_type())
+            .with_fields(&mut fields)
+            .build()?,
+    ))
+}
+
+/// Converts the `$i`-th element of `$obj`, `$depth` being its nesting depth
+/// (1 for top-level fields).
+macro_rules! element_to_value {
+    ($ft:expr, $obj:ident, $i:ident, $settings:ident, $depth:expr) => {
+        match $ft {
+            FieldType::Null => Value::Null,
+            FieldType::Bool => Value::Bool($obj.get_bool($i)?),
+            FieldType::Byte => Value::Number($obj.get_byte($i)?.into()),
+            FieldType::Short => Value::Number($obj.get_short($i)?.into()),
+            FieldType::Int => Value::Number($obj.get_int($i)?.into()),
+            FieldType::Long => long_to_value($obj.get_long($i)?, $settings),
+            FieldType::UByte => ushort_to_value($obj.get_ubyte($i)?.into(), &$settings),
+            FieldType::UShort => ushort_to_value($obj.get_ushort($i)?, &$settings),
+            FieldType::UInt => uint_to_value($obj.get_uint($i)?, &$settings)?,
+            FieldType::ULong => ulong_to_value($obj.get_ulong($i)?, &$settings)?,
+            FieldType::Float => float_to_value($obj.get_float($i)? as f64, $settings),
+            FieldType::Double => float_to_value($obj.get_double($i)?, $settings),
+            FieldType::Decimal => decimal_to_value($obj.get_decimal($i)?, $settings)?,
+            FieldType::Str => str_to_value($obj.get_string($i)?, $settings),
+            FieldType::Bytes => bytes_to_value($obj.get_bytes($i)?.data(), $settings),
+            FieldType::Date => date_to_value($settings, $obj.get_date($i)?)?,
+            // INT96 timestamps (Julian day + nanos of day) are decoded by the record reader
+            // into TimestampMillis, so they go through the same rendering.
+            FieldType::TimestampMillis => timestamp_to_value(
+                $settings,
+                $obj.get_timestamp_millis($i)? as i64,
+                TimeUnit::Millis,
+            )?,
+            FieldType::TimestampMicros => timestamp_to_value(
+                $settings,
+                $obj.get_timestamp_micros($i)? as i64,
+                TimeUnit::Micros,
+            )?,
+            FieldType::Group => row_to_value($settings, $obj.get_group($i)?, $depth)?,
+            FieldType::List => list_to_value($settings, $obj.get_list($i)?, $depth)?,
+            FieldType::Map => map_to_value($settings, $obj.get_map($i)?, $depth)?,
+        }
+    };
+}
+
+fn top_level_rows_to_json(
+    settings: &Settings,
+    values: impl Iterator<Item = Result<Value, Box<dyn Error>>>,
+    output: &mut Output,
+) -> Result<(), Box<dyn Error>> {
+    let records = values.map(|value| value.and_then(|value| value_to_json(settings, value)));
+    json_records_to_output(settings, records, output)
+}
+
+/// Whether a converted top-level row is dropped by --skip-empty-rows, having no values left
+/// (e.g. with --omit-nulls and --omit-empty-bags).
+pub(crate) fn is_skipped_empty_row(settings: &Settings, value: &Value) -> bool {
+    settings.skip_empty_rows
+        && match value {
+            Value::Null => true,
+            Value::Object(object) => object.is_empty(),
+            _ => false,
+        }
+}
+
+/// Serializes converted top-level row (an empty bag for rows omitted as null).
+pub(crate) fn value_to_json(settings: &Settings, value: Value) -> Result<String, Box<dyn Error>> {
+    let value = if value.is_null() {
+        Value::Object(serde_json::Map::default())
+    } else {
+        value
+    };
+    let json = if settings.pretty {
+        serde_json::to_string_pretty(&value)?
+    } else {
+        serde_json::to_string(&value)?
+    };
+    Ok(json)
+}
+
+fn json_records_to_output(
+    settings: &Settings,
+    records: impl Iterator<Item = Result<String, Box<dyn Error>>>,
+    output: &mut Output,
+) -> Result<(), Box<dyn Error>> {
+    // JSON array is streamed element by element, so memory stays bounded.
+    let mut rows_written = 0usize;
+    let mut part_rows = 0usize;
+    let newline = settings.newline.as_str();
+    let mut writer = output.next_writer()?;
+    start_json_part(settings, &mut writer)?;
+    for json in records {
+        let json = json?;
+        // Pretty-printed records span lines, which end with the --newline terminator as well.
+        let json = if settings.pretty && settings.newline != Newline::Lf {
+            json.replace('\n', newline)
+        } else {
+            json
+        };
+        if output.is_part_full(part_rows) {
+            end_json_part(settings, &mut writer, part_rows)?;
+            writer = output.next_writer()?;
+            start_json_part(settings, &mut writer)?;
+            part_rows = 0;
+        }
+        if settings.format == OutputFormat::JsonArray {
+            if part_rows > 0 {
+                writer.write_all(b",")?;
+            }
+            writer.write_all(newline.as_bytes())?;
+            if settings.pretty {
+                write!(
+                    writer,
+                    "  {}",
+                    json.replace(newline, &format!("{}  ", newline))
+                )?;
+            } else {
+                writer.write_all(json.as_bytes())?;
+            }
+        } else {
+            // Newlines and control characters in strings are escaped by serde_json,
+            // so a compact record is always a single physical line. Kusto multijson
+            // records are written the same way, they may just span lines with --pretty.
+            debug_assert!(
+                settings.pretty || !json.contains(|c| c == '\n' || c == '\r'),
+                "JSONL record spans multiple lines"
+            );
+            writer.write_all(json.as_bytes())?;
+            writer.write_all(newline.as_bytes())?;
+        }
+        part_rows += 1;
+        rows_written += 1;
+    }
+    end_json_part(settings, &mut writer, part_rows)?;
+    info!("{} rows written", rows_written);
+    Ok(())
+}
+
+fn start_json_part(settings: &Settings, writer: &mut dyn Write) -> io::Result<()> {
+    if settings.format == OutputFormat::JsonArray {
+        writer.write_all(b"[")?;
+    }
+    Ok(())
+}
+
+fn end_json_part(settings: &Settings, writer: &mut dyn Write, part_rows: usize) -> io::Result<()> {
+    if settings.format == OutputFormat::JsonArray {
+        let newline = settings.newline.as_str();
+        if part_rows > 0 {
+            writer.write_all(newline.as_bytes())?;
+        }
+        write!(writer, "]{}", newline)?;
+    }
+    writer.flush()
+}
+
+/// Writes CSV records of the rows, each with its input file path (if known) and its index
+/// in the file.
+fn top_level_rows_to_csv<'a>(
+    settings: &Settings,
+    rows: impl Iterator<Item = (Option<&'a str>, usize, Row)>,
+    projection: &Projection,
+    flattened: Vec<Option<Vec<String>>>,
+    explode_idx: Option<usize>,
+    header: Option<Vec<String>>,
+    output: &mut Output,
+) -> Result<(), Box<dyn Error>> {
+    let mut csv_writer = start_csv_part(settings, output, header.as_ref())?;
+    let mut rows_written = 0usize;
+    let mut part_rows = 0usize;
+    for (input_file, row_idx, row) in rows {
+        let records = csv_row_values(settings, &row, projection, &flattened, explode_idx)
+            .map_err(|e| RowError::new(input_file, row_idx, e))?;
+        let synthetic = synthetic_columns(settings, input_file, row_idx);
+        for mut record in records {
+            record.extend(synthetic.iter().map(|(_, value)| (value.clone(), None)));
+            // Every part is a complete CSV file, with its own header.
+            if output.is_part_full(part_rows) {
+                csv_writer.flush()?;
+                csv_writer = start_csv_part(settings, output, header.as_ref())?;
+                part_rows = 0;
+            }
+            for (value, leaves) in record {
+                write_csv_value(settings, &mut csv_writer, &value, leaves)?;
+            }
+            csv_writer.write_record(None::<&[u8]>)?;
+            part_rows += 1;
+            rows_written += 1;
+        }
+    }
+    csv_writer.flush()?;
+    info!("{} rows written", rows_written);
+    Ok(())
+}
+
+/// CSV record fields, every value paired with its --flatten leaves.
+type CsvRecord<'a> = Vec<(Value, Option<&'a Vec<String>>)>;
+
+/// Converts the projected columns of a row into CSV records (several with --explode).
+fn csv_row_values<'a>(
+    settings: &Settings,
+    row: &Row,
+    projection: &Projection,
+    flattened: &'a [Option<Vec<String>>],
+    explode_idx: Option<usize>,
+) -> Result<Vec<CsvRecord<'a>>, Box<dyn Error>> {
+    let mut column_idx = 0;
+    let mut values = Vec::with_capacity(row.len());
+
+    match projection.columns {
+        Some(ref cols) => {
+            // Produce empty values for columns specified by --columns argument, but missing in the file
+            for col in cols {
+                if projection.missing_columns.contains(col) {
+                    values.push((Value::Null, None));
+                } else if column_idx >= row.len() {
+                    // Projected column absent from the row is written as null as well.
+                    values.push((Value::Null, flattened[column_idx].as_ref()));
+                    column_idx += 1;
+                } else {
+                    let value =
+                        top_level_field_to_value(settings, &projection.fields, row, column_idx)
+                            .map_err(|e| ColumnError::new(row.get_field_name(column_idx), e))?;
+                    values.push((value, flattened[column_idx].as_ref()));
+                    column_idx += 1;
+                }
+            }
+        }
+        None => {
+            // No columns specified by --columns argument
+            for i in 0..row.len() {
+                let value = top_level_field_to_value(settings, &projection.fields, row, i)
+                    .map_err(|e| ColumnError::new(row.get_field_name(i), e))?;
+                values.push((value, flattened[i].as_ref()));
+            }
+        }
+    };
+
+    match explode_idx {
+        Some(idx) => {
+            let list = std::mem::replace(&mut values[idx].0, Value::Null);
+            let mut records = Vec::new();
+            for element in exploded_values(settings, list)? {
+                let mut record = values.clone();
+                record[idx].0 = element;
+                records.push(record);
+            }
+            Ok(records)
+        }
+        None => Ok(vec![values]),
+    }
+}
+
+fn start_csv_part(
+    settings: &Settings,
+    output: &mut Output,
+    header: Option<&Vec<String>>,
+) -> Result<csv::Writer<Box<dyn Write>>, Box<dyn Error>> {
+    let mut csv_writer = new_csv_writer(settings, output.next_writer()?);
+    match header {
+        // Data appended to already starts with the header.
+        Some(_) if output.is_appending() => (),
+        Some(header) => csv_writer.write_record(
+            header
+                .iter()
+                .map(|name| csv_field(settings, name.to_owned())),
+        )?,
+        None => (),
+    }
+    Ok(csv_writer)
+}
+
+/// Writes a single CSV field, or with --flatten one field per struct leaf
+/// (leaves missing in the flattened value, e.g. of a null struct, are written as nulls).
+fn write_csv_value<W: Write>(
+    settings: &Settings,
+    csv_writer: &mut csv::Writer<W>,
+    value: &Value,
+    leaves: Option<&Vec<String>>,
+) -> Result<(), Box<dyn Error>> {
+    match leaves {
+        Some(leaves) => {
+            for leaf in leaves {
+                let leaf_value = value.get(leaf.as_str()).unwrap_or(&Value::Null);
+                csv_writer.write_field(csv_field(settings, value_to_csv(settings, leaf_value)))?;
+            }
+        }
+        None => csv_writer.write_field(csv_field(settings, value_to_csv(settings, value)))?,
+    }
+    Ok(())
+}
+
+/// TSV fields are escaped, while CSV ones are quoted by the writer when needed.
+fn csv_field(settings: &Settings, field: String) -> String {
+    if settings.format != OutputFormat::Tsv
+        || !field.contains(|c: char| matches!(c, '\\' | '\t' | '\n' | '\r'))
+    {
+        return field;
+    }
+    let mut escaped = String::with_capacity(field.len() + 8);
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn new_csv_writer<W: Write>(settings: &Settings, writer: W) -> csv::Writer<W> {
+    csv::WriterBuilder::new()
+        .terminator(csv_terminator(settings.csv_terminator))
+        .delimiter(match settings.format {
+            OutputFormat::Tsv => b'\t',
+            _ => settings.csv_delimiter,
+        })
+        .quote_style(match settings.format {
+            // Fields are escaped instead, see `csv_field`.
+            OutputFormat::Tsv => QuoteStyle::Never,
+            _ => csv_quote_style(settings.csv_quote),
+        })
+        .from_writer(writer)
+}
+
+fn csv_quote_style(quote: CsvQuote) -> QuoteStyle {
+    match quote {
+        CsvQuote::Always => QuoteStyle::Always,
+        CsvQuote::Necessary => QuoteStyle::Necessary,
+        CsvQuote::NonNumeric => QuoteStyle::NonNumeric,
+        CsvQuote::Never => QuoteStyle::Never,
+    }
+}
+
+fn csv_terminator(terminator: CsvTerminator) -> Terminator {
+    match terminator {
+        CsvTerminator::Lf => Terminator::Any(b'\n'),
+        CsvTerminator::Crlf => Terminator::CRLF,
+        CsvTerminator::Cr => Terminator::Any(b'\r'),
+    }
+}
+
+fn value_to_csv(settings: &Settings, value: &Value) -> String {
+    match value {
+        Value::Null => settings.csv_null.clone(),
+        Value::Bool(v) if settings.bool_as_int => (*v as u8).to_string(),
+        Value::Bool(v) => v.to_string(),
+        Value::Number(ref v) => {
+            if v.is_f64() {
+                let mut buffer = ryu::Buffer::new();
+                truncate_trailing_zeros(buffer.format(v.as_f64().unwrap())).to_string()
+            } else if v.is_u64() {
+                format!("{}", v.as_u64().unwrap())
+            } else {
+                format!("{}", v.as_i64().unwrap())
+            }
+        }
+        Value::String(ref v) => v.to_owned(),
+        Value::Array(ref v) => serde_json::to_string(&v).unwrap(),
+        Value::Object(ref v) => serde_json::to_string(&v).unwrap(),
+    }
+}
+
+fn truncate_trailing_zeros(str: &str) -> &str {
+    str.trim_end_matches('0').trim_end_matches('.')
+}
+
+/// Adds --add-rownum-column and --add-filename-column to the `row_idx`-th converted
+/// top-level row.
+pub(crate) fn add_synthetic_columns(
+    settings: &Settings,
+    value: Value,
+    input_file: Option<&str>,
+    row_idx: usize,
+) -> Value {
+    let columns = synthetic_columns(settings, input_file, row_idx);
+    if columns.is_empty() {
+        return value;
+    }
+    let mut object = match value {
+        Value::Object(object) => object,
+        // Row omitted as an empty bag.
+        _ => serde_json::Map::default(),
+    };
+    for (name, value) in columns {
+        object.insert(name.to_owned(), value);
+    }
+    Value::Object(object)
+}
+
+/// Names and values of the synthetic columns, in output order. The row number counts
+/// the rows skipped with --resume-from and --skip, so that it's stable across --skip / --limit windows.
+/// The file name is null if the input path is not known.
+fn synthetic_columns<'a>(
+    settings: &'a Settings,
+    input_file: Option<&str>,
+    row_idx: usize,
+) -> Vec<(&'a str, Value)> {
+    let mut columns = Vec::new();
+    if let Some(ref column) = settings.rownum_column {
+        let rownum = (settings.rownum_base + row_idx) as u64;
+        columns.push((column.as_str(), Value::Number(rownum.into())));
+    }
+    if let Some(ref column) = settings.filename_column {
+        let input_file = input_file.map_or(Value::Null, |input_file| {
+            Value::String(input_file.to_owned())
+        });
+        columns.push((column.as_str(), input_file));
+    }
+    columns
+}
+
+pub(crate) fn top_level_row_to_value(
+    settings: &Settings,
+    fields: &[TypePtr],
+    row: &Row,
+) -> Result<Value, Box<dyn Error>> {
+    group_to_value(settings, row, Some(fields), 0)
+}
+
+fn row_to_value(settings: &Settings, row: &Row, depth: usize) -> Result<Value, Box<dyn Error>> {
+    group_to_value(settings, row, None, depth)
+}
+
+/// Converts a group, `top_level_fields` being the schema fields of a top-level row
+/// (`None` for nested groups).
+fn group_to_value(
+    settings: &Settings,
+    row: &Row,
+    top_level_fields: Option<&[TypePtr]>,
+    depth: usize,
+) -> Result<Value, Box<dyn Error>> {
+    check_depth(settings, depth)?;
+    // Top-level rows may end with the --where column, read only for filtering.
+    let len = top_level_fields.map_or(row.len(), |fields| fields.len().min(row.len()));
+    let mut map = serde_json::Map::with_capacity(len);
+    for i in 0..len {
+        let name = row.get_field_name(i);
+        let name = if top_level_fields.is_some() {
+            column_alias(settings, name)
+        } else {
+            name
+        };
+        let field_type = row.get_field_type(i);
+        let value = match top_level_fields {
+            Some(fields) => top_level_field_to_value(settings, fields, row, i)
+                .map_err(|e| ColumnError::new(row.get_field_name(i), e))?,
+            None => element_to_value!(field_type, row, i, settings, depth + 1),
+        };
+        match value {
+            // Nested struct is already flattened, so its keys are promoted with one prefix.
+            Value::Object(fields) if settings.flatten && matches!(field_type, FieldType::Group) => {
+                for (key, value) in fields {
+                    map.insert(
+                        format!("{}{}{}", name, settings.flatten_separator, key),
+                        value,
+                    );
+                }
+            }
+            value => {
+                let keep_null =
+                    top_level_fields.is_some() && keeps_nulls(settings, row.get_field_name(i));
+                if !(settings.omit_nulls && value.is_null()) || keep_null {
+                    map.insert(name.to_string(), value);
+                }
+            }
+        }
+    }
+
+    if settings.omit_empty_bags && map.is_empty() {
+        Ok(Value::Null)
+    } else {
+        Ok(Value::Object(map))
+    }
+}
+
+/// Converts top-level field, rendering --uuid-columns as GUID strings. Logical types
+/// which the record reader doesn't decode (TIME, JSON) are resolved with the schema `fields`
+/// of the row, so they are only rendered at the top level.
+fn top_level_field_to_value(
+    settings: &Settings,
+    fields: &[TypePtr],
+    row: &Row,
+    i: usize,
+) -> Result<Value, Box<dyn Error>> {
+    let field_type = row.get_field_type(i);
+    let logical_type = fields
+        .get(i)
+        .map(|field| field.get_basic_info().logical_type());
+    let value = match (&field_type, logical_type) {
+        (FieldType::Int, Some(LogicalType::TIME_MILLIS)) => {
+            time_to_value(settings, row.get_int(i)?.into(), TimeUnit::Millis)
+        }
+        (FieldType::Long, Some(LogicalType::TIME_MICROS)) => {
+            time_to_value(settings, row.get_long(i)?, TimeUnit::Micros)
+        }
+        (FieldType::Str, Some(LogicalType::JSON)) => Ok(json_to_value(row.get_string(i)?)),
+        (FieldType::Bytes, Some(LogicalType::JSON)) => {
+            Ok(match std::str::from_utf8(row.get_bytes(i)?.data()) {
+                Ok(json) => json_to_value(json),
+                Err(_) => bytes_to_value(row.get_bytes(i)?.data(), settings),
+            })
+        }
+        (FieldType::Bytes, _)
+            if settings
+                .uuid_columns
+                .iter()
+                .any(|column| column == row.get_field_name(i)) =>
+        {
+            uuid_to_value(row.get_bytes(i)?.data())
+        }
+        _ => Ok(element_to_value!(field_type, row, i, settings, 1)),
+    }?;
+    match settings.transforms.get(row.get_field_name(i)) {
+        Some(transform) => transformed_value(*transform, value),
+        None => Ok(value),
+    }
+}
+
+/// Applies --transform to a top-level value, nulls are kept as is.
+fn transformed_value(transform: Transform, value: Value) -> Result<Value, Box<dyn Error>> {
+    match (transform, value) {
+        (_, Value::Null) => Ok(Value::Null),
+        (Transform::Upper, Value::String(s)) => Ok(Value::String(s.to_uppercase())),
+        (Transform::Lower, Value::String(s)) => Ok(Value::String(s.to_lowercase())),
+        (Transform::Trim, Value::String(s)) => Ok(Value::String(s.trim().to_owned())),
+        // Integers are already round.
+        (Transform::Round(_), Value::Number(n)) if !n.is_f64() => Ok(Value::Number(n)),
+        (Transform::Round(digits), Value::Number(n)) => {
+            let scale = 10f64.powi(digits as i32);
+            let rounded = (n.as_f64().unwrap_or_default() * scale).round() / scale;
+            Ok(Number::from_f64(rounded).map_or(Value::Null, Value::Number))
+        }
+        (Transform::Round(_), value) => Err(format!(
+            "round() expects a number, got {} (decimals need --decimal-as-number)",
+            value
+        )
+        .into()),
+        (transform, value) => {
+            let function = match transform {
+                Transform::Upper => "upper",
+                Transform::Lower => "lower",
+                _ => "trim",
+            };
+            Err(format!("{}() expects a string, got {}", function, value).into())
+        }
+    }
+}
+
+/// Whether nulls of the top-level `column` are kept under --omit-nulls (see --keep-nulls).
+fn keeps_nulls(settings: &Settings, column: &str) -> bool {
+    settings.keep_nulls.iter().any(|keep| keep == column)
+}
+
+pub(crate) fn column_alias<'a>(settings: &'a Settings, name: &'a str) -> &'a str {
+    settings
+        .column_aliases
+        .get(name)
+        .map(|alias| alias.as_str())
+        .unwrap_or(name)
+}
+
+/// Converts a list into an array sized to its length, struct elements being sized to their
+/// field count by `group_to_value`. Element maps can't be reused, as they are moved into
+/// the array.
+fn list_to_value(settings: &Settings, list: &List, depth: usize) -> Result<Value, Box<dyn Error>> {
+    check_depth(settings, depth)?;
+    let mut arr = Vec::<Value>::with_capacity(list.len());
+    for i in 0..list.len() {
+        let elt_ty = list.get_element_type(i);
+        let value = element_to_value!(elt_ty, list, i, settings, depth + 1);
+        arr.push(value);
+    }
+
+    if settings.omit_empty_lists && arr.is_empty() {
+        Ok(Value::Null)
+    } else {
+        Ok(Value::Array(arr))
+    }
+}
+
+fn map_to_value(settings: &Settings, map: &Map, depth: usize) -> Result<Value, Box<dyn Error>> {
+    check_depth(settings, depth)?;
+    let mut jsmap = serde_json::Map::with_capacity(map.len());
+    // Keys whose values are already collected into an array (--on-duplicate-key array).
+    let mut collected_keys = HashSet::new();
+    let keys = map.get_keys();
+    let values = map.get_values();
+    for i in 0..map.len() {
+        let key_ty = keys.get_element_type(i);
+        let key = match key_ty {
+            FieldType::Null => String::from("null"),
+            FieldType::Bool => keys.get_bool(i)?.to_string(),
+            FieldType::Byte => keys.get_byte(i)?.to_string(),
+            FieldType::Short => keys.get_short(i)?.to_string(),
+            FieldType::Int => keys.get_int(i)?.to_string(),
+            FieldType::Long => keys.get_long(i)?.to_string(),
+            FieldType::UByte => keys.get_ubyte(i)?.to_string(),
+            FieldType::UShort => keys.get_ushort(i)?.to_string(),
+            FieldType::UInt => keys.get_uint(i)?.to_string(),
+            FieldType::ULong => keys.get_ulong(i)?.to_string(),
+            FieldType::Str => keys.get_string(i)?.to_string(),
+            _ if settings.lenient => {
+                // Best-effort: render the key as its JSON representation.
+                match element_to_value!(key_ty, keys, i, settings, depth + 1) {
+                    Value::String(key) => key,
+                    key => serde_json::to_string(&key)?,
+                }
+            }
+            _ => return Err(ConvertError::UnsupportedMapKey(format!("{:?}", key_ty)).into()),
+        };
+
+        let val_ty = values.get_element_type(i);
+        let value = element_to_value!(val_ty, values, i, settings, depth + 1);
+        if settings.omit_nulls && value.is_null() {
+            continue;
+        }
+        match (settings.on_duplicate_key, jsmap.entry(key)) {
+            (_, Entry::Vacant(entry)) => {
+                entry.insert(value);
+            }
+            (DuplicateKey::Last, Entry::Occupied(mut entry)) => {
+                entry.insert(value);
+            }
+            (DuplicateKey::First, Entry::Occupied(_)) => {}
+            (DuplicateKey::Error, Entry::Occupied(entry)) => {
+                return Err(format!("Duplicate map key: {}", entry.key()).into());
+            }
+            (DuplicateKey::Array, Entry::Occupied(mut entry)) => {
+                if collected_keys.contains(entry.key()) {
+                    if let Value::Array(values) = entry.get_mut() {
+                        values.push(value);
+                    }
+                } else {
+                    collected_keys.insert(entry.key().clone());
+                    let first = entry.insert(Value::Null);
+                    entry.insert(Value::Array(vec![first, value]));
+                }
+            }
+        }
+    }
+
+    if settings.omit_empty_bags && jsmap.is_empty() {
+        Ok(Value::Null)
+    } else {
+        Ok(Value::Object(jsmap))
+    }
+}
+
+/// Fails on structs, lists and maps nested deeper than --max-depth, e.g. in malformed files,
+/// rather than overflowing the stack.
+fn check_depth(settings: &Settings, depth: usize) -> Result<(), Box<dyn Error>> {
+    if depth > settings.max_depth {
+        Err(format!("Nesting depth exceeds --max-depth {}", settings.max_depth).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Renders binary values with --bytes-encoding, or as strings if they're valid UTF-8
+/// and --bytes-as-string is set.
+fn bytes_to_value(bytes: &[u8], settings: &Settings) -> Value {
+    if settings.bytes_as_string {
+        if let Ok(s) = std::str::from_utf8(bytes) {
+            return str_to_value(s, settings);
+        }
+    }
+    match settings.bytes_encoding {
+        BytesEncoding::Array => {
+            let nums = bytes
+                .iter()
+                .map(|&b| Value::Number(b.into()))
+                .collect::<Vec<_>>();
+            Value::Array(nums)
+        }
+        BytesEncoding::Base64 => Value::String(base64::encode(bytes)),
+        BytesEncoding::Hex => {
+            let mut hex = String::with_capacity(bytes.len() * 2);
+            for b in bytes {
+                hex.push_str(&format!("{:02x}", b));
+            }
+            Value::String(hex)
+        }
+    }
+}
+
+fn str_to_value(s: &str, settings: &Settings) -> Value {
+    if settings.empty_string_as_null && s.is_empty() {
+        Value::Null
+    } else {
+        Value::String(s.to_owned())
+    }
+}
+
+/// Renders 16 bytes (big-endian, as stored by Parquet UUID columns) in canonical
+/// `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` form.
+fn uuid_to_value(bytes: &[u8]) -> Result<Value, Box<dyn Error>> {
+    if bytes.len() != 16 {
+        return Err(format!("Expected UUID of 16 bytes, got {} bytes", bytes.len()).into());
+    }
+    let mut uuid = String::with_capacity(36);
+    for (i, b) in bytes.iter().enumerate() {
+        if i == 4 || i == 6 || i == 8 || i == 10 {
+            uuid.push('-');
+        }
+        uuid.push_str(&format!("{:02x}", b));
+    }
+    Ok(Value::String(uuid))
+}
+
+/// Embeds JSON text as a parsed value, falling back to the string itself if it's malformed.
+fn json_to_value(json: &str) -> Value {
+    serde_json::from_str(json).unwrap_or_else(|_| Value::String(json.to_owned()))
+}
+
+fn float_to_value(f: f64, settings: &Settings) -> Value {
+    match Number::from_f64(f) {
+        Some(n) => Value::Number(n),
+        None => match settings.float_special {
+            FloatSpecial::Null => Value::Null,
+            FloatSpecial::String => {
+                let special = if f.is_nan() {
+                    "NaN"
+                } else if f.is_sign_positive() {
+                    "Infinity"
+                } else {
+                    "-Infinity"
+                };
+                Value::String(special.to_owned())
+            }
+        },
+    }
+}
+
+// With --convert-types unsigned values are rendered as the Kusto type of their physical
+// storage (int for UINT_8/16/32, long for UINT_64, see `field_csl_schema`). Values above
+// the signed maximum are handled according to --on-overflow.
+
+fn ushort_to_value(s: u16, settings: &Settings) -> Value {
+    if settings.convert_types {
+        Value::Number(i32::from(s).into())
+    } else {
+        Value::Number(s.into())
+    }
+}
+
+fn uint_to_value(i: u32, settings: &Settings) -> Result<Value, Box<dyn Error>> {
+    if settings.convert_types && i > i32::MAX as u32 {
+        overflow_to_value(i.into(), "int", settings)
+    } else {
+        Ok(Value::Number(i.into()))
+    }
+}
+
+fn ulong_to_value(l: u64, settings: &Settings) -> Result<Value, Box<dyn Error>> {
+    if settings.convert_types && l > i64::MAX as u64 {
+        overflow_to_value(l, "long", settings)
+    } else if settings.long_as_string && l > MAX_SAFE_INTEGER as u64 {
+        Ok(Value::String(l.to_string()))
+    } else {
+        Ok(Value::Number(l.into()))
+    }
+}
+
+/// Largest integer exactly representable as IEEE 754 double (JavaScript `Number.MAX_SAFE_INTEGER`).
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// Renders 64-bit integer as JSON number, or with --long-as-string as a string
+/// if its magnitude exceeds `MAX_SAFE_INTEGER`.
+fn long_to_value(l: i64, settings: &Settings) -> Value {
+    if settings.long_as_string && !(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&l) {
+        Value::String(l.to_string())
+    } else {
+        Value::Number(l.into())
+    }
+}
+
+fn overflow_to_value(
+    value: u64,
+    kusto_type: &str,
+    settings: &Settings,
+) -> Result<Value, Box<dyn Error>> {
+    match settings.on_overflow {
+        OverflowHandling::Keep => Ok(Value::Number(value.into())),
+        OverflowHandling::String => Ok(Value::String(value.to_string())),
+        OverflowHandling::Error => {
+            Err(format!("Unsigned value {} overflows Kusto {}", value, kusto_type).into())
+        }
+    }
+}
+
+/// 100ns ticks from the .NET epoch (0001-01-01 UTC) till the Unix epoch, Kusto datetime
+/// values count ticks since the former.
+const TICKS_TILL_UNIX_TIME: u64 = 621355968000000000u64;
+
+/// Precision of a raw timestamp value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum TimeUnit {
+    Millis,
+    Micros,
+}
+
+impl TimeUnit {
+    fn units_per_second(self) -> u64 {
+        match self {
+            TimeUnit::Millis => 1_000,
+            TimeUnit::Micros => 1_000_000,
+        }
+    }
+
+    /// Number of 100ns ticks per unit.
+    fn ticks_per_unit(self) -> u64 {
+        10_000_000 / self.units_per_second()
+    }
+
+    fn nanos_per_unit(self) -> u64 {
+        1_000_000_000 / self.units_per_second()
+    }
+}
+
+/// Converts timestamp since Unix epoch to ticks since the .NET epoch, None on overflow.
+fn unix_to_ticks(ts: i64, unit: TimeUnit) -> Option<i64> {
+    ts.checked_mul(unit.ticks_per_unit() as i64)
+        .and_then(|t| t.checked_add(TICKS_TILL_UNIX_TIME as i64))
+}
+
+/// Renders timestamp since Unix epoch. The record reader returns timestamps as `u64`,
+/// reinterpreted as signed here so that pre-epoch values are handled per --pre-epoch.
+fn timestamp_to_value(
+    settings: &Settings,
+    ts: i64,
+    unit: TimeUnit,
+) -> Result<Value, Box<dyn Error>> {
+    let ts = match settings.pre_epoch {
+        _ if ts >= 0 => ts,
+        PreEpoch::Keep => ts,
+        PreEpoch::Clamp => 0,
+        PreEpoch::Null => return Ok(Value::Null),
+    };
+    let units_per_second = unit.units_per_second() as i64;
+    match settings.timestamp_rendering {
+        TimestampRendering::Ticks => {
+            let v = match unix_to_ticks(ts, unit) {
+                Some(t) if settings.ticks_as_string => Value::String(t.to_string()),
+                Some(t) => Value::Number(t.into()),
+                None => Value::Null,
+            };
+            Ok(v)
+        }
+        TimestampRendering::IsoStr => {
+            let seconds = ts.div_euclid(units_per_second);
+            let nanos = (ts.rem_euclid(units_per_second) as u64 * unit.nanos_per_unit()) as u32;
+            let datetime =
+                if let Some(dt) = chrono::NaiveDateTime::from_timestamp_opt(seconds, nanos) {
+                    dt
+                } else {
+                    return Ok(Value::Null);
+                };
+            let iso_str = match (settings.timezone, settings.timestamp_format.as_ref()) {
+                (Some(tz), format) => tz
+                    .from_utc_datetime(&datetime)
+                    .format(format.map_or("%Y-%m-%dT%H:%M:%S.%6f%:z", |f| f.as_str()))
+                    .to_string(),
+                (None, format) => datetime
+                    .format(format.map_or("%Y-%m-%dT%H:%M:%S.%6fZ", |f| f.as_str()))
+                    .to_string(),
+            };
+            Ok(Value::String(iso_str))
+        }
+        TimestampRendering::UnixMs => {
+            let millis = ts.div_euclid(units_per_second / 1000);
+            Ok(Value::Number(millis.into()))
+        }
+    }
+}
+
+/// Renders time of day as `HH:MM:SS.fff` (or `.ffffff` for microseconds), unless
+/// --time-as-number is set. Values outside of a day (negative or 24h and later)
+/// are rejected rather than clamped, as they don't represent a valid TIME.
+fn time_to_value(settings: &Settings, time: i64, unit: TimeUnit) -> Result<Value, Box<dyn Error>> {
+    if settings.time_as_number {
+        return Ok(Value::Number(time.into()));
+    }
+    let units_per_second = unit.units_per_second() as i64;
+    if time < 0 || time >= 24 * 60 * 60 * units_per_second {
+        return Err(format!("Time of day {} is out of range", time).into());
+    }
+    let seconds = time / units_per_second;
+    let fraction = time % units_per_second;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    let time = match unit {
+        TimeUnit::Millis => format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, fraction),
+        TimeUnit::Micros => format!("{:02}:{:02}:{:02}.{:06}", hours, minutes, seconds, fraction),
+    };
+    Ok(Value::String(time))
+}
+
+/// Renders date as `%Y-%m-%d` string, or with ticks and unixms timestamp rendering,
+/// as the timestamp of its midnight (UTC). The record reader returns dates as `u32`,
+/// reinterpreted as signed here so that pre-epoch dates are kept.
+fn date_to_value(settings: &Settings, days_from_epoch: u32) -> Result<Value, Box<dyn Error>> {
+    const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+    let days_from_epoch = days_from_epoch as i32 as i64;
+    match settings.timestamp_rendering {
+        TimestampRendering::Ticks | TimestampRendering::UnixMs => {
+            return timestamp_to_value(
+                settings,
+                days_from_epoch * MILLIS_PER_DAY,
+                TimeUnit::Millis,
+            );
+        }
+        TimestampRendering::IsoStr => (),
+    }
+    let date = match chrono::NaiveDate::from_ymd(1970, 1, 1)
+        .checked_add_signed(Duration::days(days_from_epoch))
+    {
+        Some(date) => date,
+        None => return Ok(Value::Null),
+    };
+    let iso_str = date.format("%Y-%m-%d").to_string();
+    Ok(Value::String(iso_str))
+}
+
+fn decimal_to_value(decimal: &Decimal, settings: &Settings) -> Result<Value, Box<dyn Error>> {
+    let decimal_str = decimal_to_string(decimal)?;
+    if settings.decimal_as_number {
+        if let Some(number) = decimal_str_to_number(&decimal_str) {
+            return Ok(Value::Number(number));
+        }
+    }
+    Ok(Value::String(decimal_str))
+}
+
+/// Converts decimal string into JSON number, only when it is representable without
+/// precision loss (as i64, or as f64 that prints back to the same digits).
+fn decimal_str_to_number(decimal_str: &str) -> Option<Number> {
+    let normalized = if decimal_str.contains('.') {
+        truncate_trailing_zeros(decimal_str)
+    } else {
+        decimal_str
+    };
+    if let Ok(n) = normalized.parse::<i64>() {
+        return Some(n.into());
+    }
+    let f = normalized.parse::<f64>().ok()?;
+    let mut buffer = ryu::Buffer::new();
+    if truncate_trailing_zeros(buffer.format(f)) == normalized {
+        Number::from_f64(f)
+    } else {
+        None
+    }
+}
+
+fn decimal_to_string(decimal: &Decimal) -> Result<String, Box<dyn Error>> {
+    // Precision equal to scale is valid (e.g. 0.99 with precision 2 and scale 2).
+    if decimal.scale() < 0 || decimal.precision() < decimal.scale() {
+        return Err(ConvertError::InvalidDecimal {
+            precision: decimal.precision(),
+            scale: decimal.scale(),
+        }
+        .into());
+    }
+
+    // Specify as signed bytes to resolve sign as part of conversion.
+    let num = BigInt::from_signed_bytes_be(decimal.data());
+
+    // Offset of the first digit in a string.
+    let negative = if num.sign() == Sign::Minus { 1 } else { 0 };
+    let mut num_str = num.to_string();
+    if decimal.scale() == 0 {
+        // Integral value, no decimal point.
+        return Ok(num_str);
+    }
+    let mut point = num_str.len() as i32 - decimal.scale() - negative;
+
+    // Convert to string form without scientific notation.
     if point <= 0 {
         // Zeros need to be prepended to the unscaled value.
         while point < 0 {
@@ -367,5 +2037,959 @@ fn decimal_to_string(decimal: &Decimal) -> String {
         num_str.insert((point + negative) as usize, '.');
     }
 
-    num_str
+    Ok(num_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{write_batch, Fixture};
+    use parquet::data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type, Int96, Int96Type};
+    use parquet::schema::parser::parse_message_type;
+    use std::time::Instant;
+
+    #[test]
+    fn decimal_precision_equal_to_scale() {
+        let decimal = Decimal::from_i32(99, 2, 2);
+        assert_eq!(decimal_to_string(&decimal).unwrap(), "0.99");
+        let decimal = Decimal::from_i32(-5, 3, 3);
+        assert_eq!(decimal_to_string(&decimal).unwrap(), "-0.005");
+        let decimal = Decimal::from_i64(1, 18, 18);
+        assert_eq!(decimal_to_string(&decimal).unwrap(), "0.000000000000000001");
+    }
+
+    #[test]
+    fn decimal_zero_scale() {
+        let decimal = Decimal::from_i32(12345, 5, 0);
+        assert_eq!(decimal_to_string(&decimal).unwrap(), "12345");
+        let decimal = Decimal::from_i64(-1234567890123, 18, 0);
+        assert_eq!(decimal_to_string(&decimal).unwrap(), "-1234567890123");
+        let decimal = Decimal::from_bytes(ByteArray::from(vec![0xFF, 0x00]), 4, 0);
+        assert_eq!(decimal_to_string(&decimal).unwrap(), "-256");
+    }
+
+    #[test]
+    fn decimal_fraction() {
+        let decimal = Decimal::from_i64(12345, 10, 2);
+        assert_eq!(decimal_to_string(&decimal).unwrap(), "123.45");
+        let decimal = Decimal::from_bytes(ByteArray::from(vec![0x01, 0x00]), 5, 2);
+        assert_eq!(decimal_to_string(&decimal).unwrap(), "2.56");
+    }
+
+    #[test]
+    fn decimal_scale_exceeding_precision_is_invalid() {
+        let decimal = Decimal::from_i32(1, 2, 3);
+        assert!(decimal_to_string(&decimal).is_err());
+    }
+
+    fn overflow_settings(on_overflow: OverflowHandling) -> Settings {
+        Settings {
+            convert_types: true,
+            on_overflow,
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn ulong_within_long_range_is_a_number() {
+        for on_overflow in &[
+            OverflowHandling::Keep,
+            OverflowHandling::String,
+            OverflowHandling::Error,
+        ] {
+            let settings = overflow_settings(*on_overflow);
+            assert_eq!(
+                ulong_to_value(i64::MAX as u64, &settings).unwrap(),
+                Value::Number(i64::MAX.into())
+            );
+        }
+    }
+
+    #[test]
+    fn ulong_overflow_keep() {
+        let settings = overflow_settings(OverflowHandling::Keep);
+        for value in &[i64::MAX as u64 + 1, u64::MAX] {
+            assert_eq!(
+                ulong_to_value(*value, &settings).unwrap(),
+                Value::Number((*value).into())
+            );
+        }
+    }
+
+    #[test]
+    fn ulong_overflow_string() {
+        let settings = overflow_settings(OverflowHandling::String);
+        assert_eq!(
+            ulong_to_value(i64::MAX as u64 + 1, &settings).unwrap(),
+            Value::String(String::from("9223372036854775808"))
+        );
+        assert_eq!(
+            ulong_to_value(u64::MAX, &settings).unwrap(),
+            Value::String(String::from("18446744073709551615"))
+        );
+    }
+
+    #[test]
+    fn ulong_overflow_error() {
+        let settings = overflow_settings(OverflowHandling::Error);
+        assert!(ulong_to_value(i64::MAX as u64 + 1, &settings).is_err());
+        assert!(ulong_to_value(u64::MAX, &settings).is_err());
+    }
+
+    #[test]
+    fn ulong_without_convert_types_is_unsigned() {
+        let settings = Settings {
+            on_overflow: OverflowHandling::Error,
+            ..Settings::default()
+        };
+        assert_eq!(
+            ulong_to_value(u64::MAX, &settings).unwrap(),
+            Value::Number(u64::MAX.into())
+        );
+    }
+
+    fn csv_bytes(settings: &Settings, records: &[&[&str]]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut writer = new_csv_writer(settings, &mut bytes);
+            for record in records {
+                writer.write_record(*record).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn csv_terminators() {
+        let records: &[&[&str]] = &[&["a", "b"], &["c", "d"]];
+        let lf = Settings {
+            format: OutputFormat::Csv,
+            csv_terminator: CsvTerminator::Lf,
+            ..Settings::default()
+        };
+        assert_eq!(csv_bytes(&lf, records), b"a,b\nc,d\n");
+        let crlf = Settings {
+            csv_terminator: CsvTerminator::Crlf,
+            ..lf.clone()
+        };
+        assert_eq!(csv_bytes(&crlf, records), b"a,b\r\nc,d\r\n");
+        let cr = Settings {
+            csv_terminator: CsvTerminator::Cr,
+            ..lf
+        };
+        assert_eq!(csv_bytes(&cr, records), b"a,b\rc,d\r");
+    }
+
+    fn quoted_csv(csv_quote: CsvQuote) -> Vec<u8> {
+        let settings = Settings {
+            format: OutputFormat::Csv,
+            csv_quote,
+            ..Settings::default()
+        };
+        csv_bytes(&settings, &[&["1", "a b", "x,y", "q\""]])
+    }
+
+    #[test]
+    fn csv_quote_necessary() {
+        assert_eq!(
+            quoted_csv(CsvQuote::Necessary),
+            b"1,a b,\"x,y\",\"q\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn csv_quote_always() {
+        assert_eq!(
+            quoted_csv(CsvQuote::Always),
+            b"\"1\",\"a b\",\"x,y\",\"q\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn csv_quote_non_numeric() {
+        assert_eq!(
+            quoted_csv(CsvQuote::NonNumeric),
+            b"1,\"a b\",\"x,y\",\"q\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn csv_quote_never() {
+        assert_eq!(quoted_csv(CsvQuote::Never), b"1,a b,x,y,q\"\n");
+    }
+
+    #[test]
+    fn tsv_is_never_quoted() {
+        let settings = Settings {
+            format: OutputFormat::Tsv,
+            csv_quote: CsvQuote::Always,
+            ..Settings::default()
+        };
+        assert_eq!(csv_bytes(&settings, &[&["1", "a b"]]), b"1\ta b\n");
+    }
+
+    #[test]
+    fn ticks_of_unix_epoch() {
+        assert_eq!(unix_to_ticks(0, TimeUnit::Millis), Some(621355968000000000));
+        assert_eq!(unix_to_ticks(0, TimeUnit::Micros), Some(621355968000000000));
+    }
+
+    #[test]
+    fn ticks_of_known_date() {
+        // 2021-01-01T00:00:00Z
+        assert_eq!(
+            unix_to_ticks(1609459200000, TimeUnit::Millis),
+            Some(637450560000000000)
+        );
+        assert_eq!(
+            unix_to_ticks(1609459200000123, TimeUnit::Micros),
+            Some(637450560000001230)
+        );
+    }
+
+    #[test]
+    fn ticks_before_unix_epoch() {
+        // 1969-12-31T23:59:59Z
+        assert_eq!(
+            unix_to_ticks(-1000, TimeUnit::Millis),
+            Some(621355967990000000)
+        );
+        // 0001-01-01T00:00:00Z, the .NET epoch.
+        assert_eq!(unix_to_ticks(-62135596800000, TimeUnit::Millis), Some(0));
+    }
+
+    #[test]
+    fn ticks_overflow_boundary() {
+        let max_millis = (i64::MAX - TICKS_TILL_UNIX_TIME as i64) / 10_000;
+        assert_eq!(
+            unix_to_ticks(max_millis, TimeUnit::Millis),
+            Some(max_millis * 10_000 + TICKS_TILL_UNIX_TIME as i64)
+        );
+        assert_eq!(unix_to_ticks(max_millis + 1, TimeUnit::Millis), None);
+        assert_eq!(unix_to_ticks(i64::MAX, TimeUnit::Millis), None);
+        assert_eq!(unix_to_ticks(i64::MIN, TimeUnit::Micros), None);
+    }
+
+    fn tsv_settings() -> Settings {
+        Settings {
+            format: OutputFormat::Tsv,
+            ..Settings::default()
+        }
+    }
+
+    fn tsv_unescape(field: &str) -> String {
+        let mut unescaped = String::with_capacity(field.len());
+        let mut chars = field.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('\\') => unescaped.push('\\'),
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                other => panic!("invalid TSV escape: \\{:?}", other),
+            }
+        }
+        unescaped
+    }
+
+    #[test]
+    fn tsv_field_escaping() {
+        let settings = tsv_settings();
+        assert_eq!(csv_field(&settings, "plain".to_string()), "plain");
+        assert_eq!(csv_field(&settings, "a\tb".to_string()), "a\\tb");
+        assert_eq!(csv_field(&settings, "a\nb\r\n".to_string()), "a\\nb\\r\\n");
+        assert_eq!(csv_field(&settings, "C:\\tmp".to_string()), "C:\\\\tmp");
+        assert_eq!(csv_field(&settings, "\\t".to_string()), "\\\\t");
+    }
+
+    #[test]
+    fn tsv_field_round_trip() {
+        let settings = tsv_settings();
+        let fields = [
+            "",
+            "plain",
+            "tab\there",
+            "line\nbreak",
+            "crlf\r\n",
+            "back\\slash",
+            "\\n is not a newline",
+            "\t\\\n\r\\\\",
+            "quote \" and comma ,",
+        ];
+        let escaped: Vec<String> = fields
+            .iter()
+            .map(|field| csv_field(&settings, field.to_string()))
+            .collect();
+        let record: Vec<&str> = escaped.iter().map(String::as_str).collect();
+        let bytes = csv_bytes(&settings, &[&record]);
+        let line = String::from_utf8(bytes).unwrap();
+        let line = line.strip_suffix('\n').unwrap();
+        assert!(!line.contains('\n') && !line.contains('\r'));
+        let parsed: Vec<String> = line.split('\t').map(tsv_unescape).collect();
+        assert_eq!(parsed, fields);
+    }
+
+    #[test]
+    fn csv_fields_are_not_escaped() {
+        let settings = Settings {
+            format: OutputFormat::Csv,
+            ..Settings::default()
+        };
+        assert_eq!(csv_field(&settings, "a\tb\\\n".to_string()), "a\tb\\\n");
+    }
+
+    fn abc_fixture(name: &str) -> Fixture {
+        Fixture::new(
+            name,
+            "message schema {
+                REQUIRED INT32 a;
+                REQUIRED INT32 b;
+                REQUIRED INT32 c;
+            }",
+            1,
+            |_, column, writer| {
+                let base = [1, 10, 100][column];
+                write_batch::<Int32Type>(writer, &[base, base * 2], None, None);
+            },
+        )
+    }
+
+    #[test]
+    fn json_keys_follow_columns_order() {
+        let fixture = abc_fixture("json-keys-columns-order");
+        let settings = Settings {
+            columns: Some(vec![String::from("b"), String::from("a")]),
+            ..Settings::default()
+        };
+        assert_eq!(
+            fixture.convert(&settings).unwrap(),
+            "{\"b\":10,\"a\":1}\n{\"b\":20,\"a\":2}\n"
+        );
+    }
+
+    #[test]
+    fn json_keys_follow_schema_order() {
+        let fixture = abc_fixture("json-keys-schema-order");
+        let settings = Settings {
+            columns: Some(vec![
+                String::from("c"),
+                String::from("a"),
+                String::from("b"),
+            ]),
+            ..Settings::default()
+        };
+        assert_eq!(
+            fixture.convert(&settings).unwrap(),
+            "{\"c\":100,\"a\":1,\"b\":10}\n{\"c\":200,\"a\":2,\"b\":20}\n"
+        );
+        assert_eq!(
+            fixture.convert(&Settings::default()).unwrap(),
+            "{\"a\":1,\"b\":10,\"c\":100}\n{\"a\":2,\"b\":20,\"c\":200}\n"
+        );
+    }
+
+    #[test]
+    fn jsonl_records_are_single_lines() {
+        let strings = [
+            "line\nbreak",
+            "crlf\r\n",
+            "cr\ronly",
+            "\u{0}\u{1}\u{1f}\t\u{7f}",
+            "\u{2028}\u{2029}",
+            "\n",
+        ];
+        let fixture = Fixture::new(
+            "jsonl-single-lines",
+            "message schema {
+                REQUIRED BYTE_ARRAY s (UTF8);
+            }",
+            1,
+            |_, _, writer| {
+                let values = strings.iter().map(|s| ByteArray::from(*s)).collect_vec();
+                write_batch::<ByteArrayType>(writer, &values, None, None);
+            },
+        );
+        let output = fixture.convert(&Settings::default()).unwrap();
+        let lines = output.strip_suffix('\n').unwrap().split('\n').collect_vec();
+        assert_eq!(lines.len(), strings.len());
+        for (line, s) in lines.iter().zip(strings.iter()) {
+            assert!(!line.contains('\r'));
+            let record: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(record["s"], *s);
+        }
+    }
+
+    #[test]
+    fn csv_writes_null_for_column_absent_from_row() {
+        let fixture = abc_fixture("csv-column-absent-from-row");
+        let settings = Settings {
+            format: OutputFormat::Csv,
+            columns: Some(vec![String::from("a"), String::from("b")]),
+            ..Settings::default()
+        };
+        let reader = fixture.reader();
+        let projection = Projection::new(&settings, reader.as_ref()).unwrap();
+        let flattened = flattened_fields(&settings, &projection.fields);
+        // Rows read with a narrower schema than the projection lack column `b`.
+        let narrow_schema = parse_message_type(
+            "message schema {
+                REQUIRED INT32 a;
+            }",
+        )
+        .unwrap();
+        let row = reader
+            .get_row_iter(Some(narrow_schema))
+            .unwrap()
+            .next()
+            .unwrap();
+        let records = csv_row_values(&settings, &row, &projection, &flattened, None).unwrap();
+        let values = records[0].iter().map(|(value, _)| value).collect_vec();
+        assert_eq!(values, vec![&Value::from(1), &Value::Null]);
+    }
+
+    #[test]
+    fn csv_writes_null_for_column_missing_in_file() {
+        let fixture = abc_fixture("csv-column-missing-in-file");
+        let settings = Settings {
+            format: OutputFormat::Csv,
+            columns: Some(vec![String::from("a"), String::from("x")]),
+            csv_null: String::from("NULL"),
+            ..Settings::default()
+        };
+        assert_eq!(fixture.convert(&settings).unwrap(), "1,NULL\n2,NULL\n");
+    }
+
+    #[test]
+    fn legacy_repeated_primitive_is_array() {
+        let fixture = Fixture::new(
+            "legacy-repeated-primitive",
+            "message schema {
+                REPEATED INT32 r;
+                REQUIRED INT32 id;
+            }",
+            1,
+            |_, column, writer| match column {
+                0 => write_batch::<Int32Type>(
+                    writer,
+                    &[1, 2, 3, 4],
+                    Some(&[1, 1, 1, 0, 1]),
+                    Some(&[0, 1, 1, 0, 0]),
+                ),
+                _ => write_batch::<Int32Type>(writer, &[1, 2, 3], None, None),
+            },
+        );
+        assert_eq!(
+            fixture.convert(&Settings::default()).unwrap(),
+            "{\"r\":[1,2,3],\"id\":1}\n{\"r\":[],\"id\":2}\n{\"r\":[4],\"id\":3}\n"
+        );
+    }
+
+    fn duplicate_key_output(
+        name: &str,
+        on_duplicate_key: DuplicateKey,
+    ) -> Result<String, ConvertError> {
+        // A single map {a: 1, b: 2, a: 3, a: 4}.
+        let fixture = Fixture::new(
+            name,
+            "message schema {
+                REQUIRED group m (MAP) {
+                    REPEATED group key_value {
+                        REQUIRED BYTE_ARRAY key (UTF8);
+                        OPTIONAL INT32 value;
+                    }
+                }
+            }",
+            1,
+            |_, column, writer| match column {
+                0 => {
+                    let keys = ["a", "b", "a", "a"]
+                        .iter()
+                        .map(|key| ByteArray::from(*key))
+                        .collect_vec();
+                    write_batch::<ByteArrayType>(
+                        writer,
+                        &keys,
+                        Some(&[1, 1, 1, 1]),
+                        Some(&[0, 1, 1, 1]),
+                    )
+                }
+                _ => write_batch::<Int32Type>(
+                    writer,
+                    &[1, 2, 3, 4],
+                    Some(&[2, 2, 2, 2]),
+                    Some(&[0, 1, 1, 1]),
+                ),
+            },
+        );
+        let settings = Settings {
+            on_duplicate_key,
+            ..Settings::default()
+        };
+        fixture.convert(&settings)
+    }
+
+    #[test]
+    fn duplicate_map_key_last() {
+        let output = duplicate_key_output("duplicate-key-last", DuplicateKey::Last);
+        assert_eq!(output.unwrap(), "{\"m\":{\"a\":4,\"b\":2}}\n");
+    }
+
+    #[test]
+    fn duplicate_map_key_first() {
+        let output = duplicate_key_output("duplicate-key-first", DuplicateKey::First);
+        assert_eq!(output.unwrap(), "{\"m\":{\"a\":1,\"b\":2}}\n");
+    }
+
+    #[test]
+    fn duplicate_map_key_error() {
+        let output = duplicate_key_output("duplicate-key-error", DuplicateKey::Error);
+        let message = output.unwrap_err().to_string();
+        assert!(message.contains("Duplicate map key: a"), "{}", message);
+    }
+
+    #[test]
+    fn duplicate_map_key_array() {
+        let output = duplicate_key_output("duplicate-key-array", DuplicateKey::Array);
+        assert_eq!(output.unwrap(), "{\"m\":{\"a\":[1,3,4],\"b\":2}}\n");
+    }
+
+    fn empty_fixture(name: &str, row_groups: usize) -> Fixture {
+        Fixture::new(
+            name,
+            "message schema {
+                REQUIRED INT32 a;
+                OPTIONAL BYTE_ARRAY b (UTF8);
+            }",
+            row_groups,
+            |_, column, writer| match column {
+                0 => write_batch::<Int32Type>(writer, &[], None, None),
+                _ => write_batch::<ByteArrayType>(writer, &[], Some(&[]), None),
+            },
+        )
+    }
+
+    fn empty_output(fixture: &Fixture, format: OutputFormat) -> String {
+        let settings = Settings {
+            format,
+            csv_header: true,
+            ..Settings::default()
+        };
+        fixture.convert(&settings).unwrap()
+    }
+
+    #[test]
+    fn header_only_file_output_is_well_formed() {
+        let fixture = empty_fixture("header-only", 0);
+        assert_eq!(empty_output(&fixture, OutputFormat::Csv), "a,b\n");
+        assert_eq!(empty_output(&fixture, OutputFormat::Tsv), "a\tb\n");
+        assert_eq!(empty_output(&fixture, OutputFormat::JsonArray), "[]\n");
+        assert_eq!(empty_output(&fixture, OutputFormat::Jsonl), "");
+    }
+
+    #[test]
+    fn empty_row_group_output_is_well_formed() {
+        let fixture = empty_fixture("empty-row-group", 1);
+        assert_eq!(empty_output(&fixture, OutputFormat::Csv), "a,b\n");
+        assert_eq!(empty_output(&fixture, OutputFormat::JsonArray), "[]\n");
+        assert_eq!(empty_output(&fixture, OutputFormat::Jsonl), "");
+    }
+
+    fn integer_decimal_fixture(name: &str) -> Fixture {
+        Fixture::new(
+            name,
+            "message schema {
+                REQUIRED INT32 d32 (DECIMAL(5,2));
+                REQUIRED INT64 d64 (DECIMAL(18,4));
+            }",
+            1,
+            |_, column, writer| match column {
+                0 => write_batch::<Int32Type>(writer, &[12345, -5, 0], None, None),
+                _ => write_batch::<Int64Type>(writer, &[1234567890123456, -10000, 5], None, None),
+            },
+        )
+    }
+
+    #[test]
+    fn integer_backed_decimals_keep_scale() {
+        let fixture = integer_decimal_fixture("integer-decimals");
+        assert_eq!(
+            fixture.convert(&Settings::default()).unwrap(),
+            "{\"d32\":\"123.45\",\"d64\":\"123456789012.3456\"}\n\
+             {\"d32\":\"-0.05\",\"d64\":\"-1.0000\"}\n\
+             {\"d32\":\"0.00\",\"d64\":\"0.0005\"}\n"
+        );
+    }
+
+    #[test]
+    fn integer_backed_decimals_as_numbers() {
+        let fixture = integer_decimal_fixture("integer-decimals-as-numbers");
+        let settings = Settings {
+            decimal_as_number: true,
+            ..Settings::default()
+        };
+        assert_eq!(
+            fixture.convert(&settings).unwrap(),
+            "{\"d32\":123.45,\"d64\":123456789012.3456}\n\
+             {\"d32\":-0.05,\"d64\":-1}\n\
+             {\"d32\":0,\"d64\":0.0005}\n"
+        );
+    }
+
+    fn int96_output(name: &str, timestamp_rendering: TimestampRendering) -> String {
+        let fixture = Fixture::new(
+            name,
+            "message schema {
+                OPTIONAL INT96 ts;
+            }",
+            1,
+            |_, _, writer| {
+                // 2021-01-01T12:00:00.123Z: Julian day 2459216, 43200123000000 nanos of day.
+                let mut ts = Int96::new();
+                ts.set_data(1341936832, 10058, 2459216);
+                write_batch::<Int96Type>(writer, &[ts], Some(&[1, 0]), None);
+            },
+        );
+        let settings = Settings {
+            timestamp_rendering,
+            ..Settings::default()
+        };
+        fixture.convert(&settings).unwrap()
+    }
+
+    #[test]
+    fn int96_timestamp_as_ticks() {
+        assert_eq!(
+            int96_output("int96-ticks", TimestampRendering::Ticks),
+            "{\"ts\":637450992001230000}\n{\"ts\":null}\n"
+        );
+    }
+
+    #[test]
+    fn int96_timestamp_as_iso_string() {
+        assert_eq!(
+            int96_output("int96-iso", TimestampRendering::IsoStr),
+            "{\"ts\":\"2021-01-01T12:00:00.123000Z\"}\n{\"ts\":null}\n"
+        );
+    }
+
+    #[test]
+    fn int96_timestamp_as_unix_millis() {
+        assert_eq!(
+            int96_output("int96-unix-ms", TimestampRendering::UnixMs),
+            "{\"ts\":1609502400123}\n{\"ts\":null}\n"
+        );
+    }
+
+    /// Timing of the CSV writer created for every row versus a single writer,
+    /// see "Timing harnesses" in the README.
+    #[test]
+    #[ignore]
+    fn csv_writer_reuse_timing() {
+        let settings = Settings {
+            format: OutputFormat::Csv,
+            ..Settings::default()
+        };
+        let record = (0..50)
+            .map(|i| format!("value \"{}\", quoted", i))
+            .collect_vec();
+        let rows = 200_000;
+
+        let start = Instant::now();
+        let mut per_row = Vec::new();
+        for _ in 0..rows {
+            let mut csv_writer = new_csv_writer(&settings, Vec::new());
+            csv_writer.write_record(&record).unwrap();
+            let bytes = csv_writer.into_inner().map_err(|e| e.to_string()).unwrap();
+            per_row.write_all(&bytes).unwrap();
+        }
+        let per_row_elapsed = start.elapsed();
+
+        let start = Instant::now();
+        let mut single = Vec::new();
+        {
+            let mut csv_writer = new_csv_writer(&settings, &mut single);
+            for _ in 0..rows {
+                csv_writer.write_record(&record).unwrap();
+            }
+            csv_writer.flush().unwrap();
+        }
+        let single_elapsed = start.elapsed();
+
+        assert_eq!(per_row, single);
+        println!(
+            "{} rows of {} columns: writer per row {:?}, single writer {:?}",
+            rows,
+            record.len(),
+            per_row_elapsed,
+            single_elapsed
+        );
+    }
+
+    #[test]
+    fn time_is_rendered_at_top_level_only() {
+        let fixture = Fixture::new(
+            "time-top-level-only",
+            "message schema {
+                REQUIRED INT32 t (TIME_MILLIS);
+                REQUIRED INT64 t_us (TIME_MICROS);
+                REQUIRED group nested {
+                    REQUIRED INT32 t (TIME_MILLIS);
+                }
+            }",
+            1,
+            |_, column, writer| match column {
+                1 => write_batch::<Int64Type>(writer, &[3723000004], None, None),
+                _ => write_batch::<Int32Type>(writer, &[3723004], None, None),
+            },
+        );
+        assert_eq!(
+            fixture.convert(&Settings::default()).unwrap(),
+            "{\"t\":\"01:02:03.004\",\"t_us\":\"01:02:03.000004\",\"nested\":{\"t\":3723004}}\n"
+        );
+    }
+
+    #[test]
+    fn json_is_embedded_at_top_level_only() {
+        let fixture = Fixture::new(
+            "json-top-level-only",
+            "message schema {
+                REQUIRED BYTE_ARRAY j (JSON);
+                REQUIRED group nested {
+                    REQUIRED BYTE_ARRAY j (JSON);
+                }
+            }",
+            1,
+            |_, _, writer| {
+                let values = ["{\"a\":[1,2]}", "not json"]
+                    .iter()
+                    .map(|json| ByteArray::from(*json))
+                    .collect_vec();
+                write_batch::<ByteArrayType>(writer, &values, None, None);
+            },
+        );
+        assert_eq!(
+            fixture.convert(&Settings::default()).unwrap(),
+            "{\"j\":{\"a\":[1,2]},\"nested\":{\"j\":\"{\\\"a\\\":[1,2]}\"}}\n\
+             {\"j\":\"not json\",\"nested\":{\"j\":\"not json\"}}\n"
+        );
+    }
+
+    /// Two row groups of three rows, the fifth row (index 4) has a TIME out of range.
+    fn invalid_row_fixture(name: &str) -> Fixture {
+        Fixture::new(
+            name,
+            "message schema {
+                REQUIRED INT32 t (TIME_MILLIS);
+            }",
+            2,
+            |row_group, _, writer| {
+                let values = match row_group {
+                    0 => [0, 1, 2],
+                    _ => [3, 24 * 60 * 60 * 1000, 5],
+                };
+                write_batch::<Int32Type>(writer, &values, None, None);
+            },
+        )
+    }
+
+    fn failed_row(fixture: &Fixture, settings: &Settings) -> RowError {
+        match fixture.convert(settings) {
+            Err(ConvertError::Row(e)) => e,
+            result => panic!("expected a row error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn row_error_has_file_row_index() {
+        let fixture = invalid_row_fixture("row-error-index");
+        let e = failed_row(&fixture, &Settings::default());
+        assert_eq!(e.row, 4);
+        assert_eq!(e.file.as_deref(), Some(fixture.path()));
+        assert_eq!(e.column.as_deref(), Some("t"));
+        assert!(e
+            .to_string()
+            .starts_with(&format!("{}: Row 4, column t: ", fixture.path())));
+    }
+
+    #[test]
+    fn row_error_index_counts_skipped_rows() {
+        let fixture = invalid_row_fixture("row-error-index-skipped");
+        let settings = Settings {
+            row_groups: Some(vec![1]),
+            ..Settings::default()
+        };
+        assert_eq!(failed_row(&fixture, &settings).row, 4);
+        let settings = Settings {
+            skip: 2,
+            ..Settings::default()
+        };
+        assert_eq!(failed_row(&fixture, &settings).row, 4);
+        let settings = Settings {
+            resume_from: 4,
+            ..Settings::default()
+        };
+        assert_eq!(failed_row(&fixture, &settings).row, 4);
+        let settings = Settings {
+            format: OutputFormat::Csv,
+            skip: 1,
+            ..Settings::default()
+        };
+        assert_eq!(failed_row(&fixture, &settings).row, 4);
+    }
+
+    #[test]
+    fn validate_reports_file_row_index() {
+        let fixture = invalid_row_fixture("validate-row-index");
+        let settings = Settings {
+            row_groups: Some(vec![1]),
+            skip: 1,
+            ..Settings::default()
+        };
+        match validate_files(&settings, &[fixture.path()]) {
+            Err(ConvertError::Row(e)) => {
+                assert_eq!(e.row, 4);
+                assert_eq!(e.file.as_deref(), Some(fixture.path()));
+            }
+            result => panic!("expected a row error, got {:?}", result),
+        }
+    }
+
+    /// Two row groups of three rows, with ids 0 to 5.
+    fn ids_fixture(name: &str) -> Fixture {
+        Fixture::new(
+            name,
+            "message schema {
+                REQUIRED INT32 id;
+            }",
+            2,
+            |row_group, _, writer| {
+                let first = row_group as i32 * 3;
+                write_batch::<Int32Type>(writer, &[first, first + 1, first + 2], None, None);
+            },
+        )
+    }
+
+    fn rownum_settings() -> Settings {
+        Settings {
+            rownum_column: Some(String::from("n")),
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn rownum_is_row_index_in_file() {
+        let fixture = ids_fixture("rownum-index");
+        let output = fixture.convert(&rownum_settings()).unwrap();
+        let expected = (0..6)
+            .map(|i| format!("{{\"id\":{},\"n\":{}}}\n", i, i))
+            .join("");
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn rownum_counts_unselected_rows() {
+        let fixture = ids_fixture("rownum-unselected");
+        let settings = Settings {
+            row_groups: Some(vec![1]),
+            filter: Some(RowFilter::parse("id!=4").unwrap()),
+            skip: 1,
+            rownum_base: 1,
+            ..rownum_settings()
+        };
+        assert_eq!(fixture.convert(&settings).unwrap(), "{\"id\":5,\"n\":6}\n");
+        let settings = Settings {
+            tail: Some(2),
+            ..rownum_settings()
+        };
+        assert_eq!(
+            fixture.convert(&settings).unwrap(),
+            "{\"id\":4,\"n\":4}\n{\"id\":5,\"n\":5}\n"
+        );
+        let settings = Settings {
+            format: OutputFormat::Csv,
+            resume_from: 4,
+            ..rownum_settings()
+        };
+        assert_eq!(fixture.convert(&settings).unwrap(), "4,4\n5,5\n");
+    }
+
+    #[test]
+    fn rownum_with_threads_matches_single_threaded() {
+        let fixture = ids_fixture("rownum-threads");
+        let settings = Settings {
+            threads: 2,
+            filter: Some(RowFilter::parse("id>=2").unwrap()),
+            ..rownum_settings()
+        };
+        let single = Settings {
+            threads: 1,
+            ..settings.clone()
+        };
+        assert_eq!(
+            fixture.convert(&settings).unwrap(),
+            fixture.convert(&single).unwrap()
+        );
+    }
+
+    fn date_settings(timestamp_rendering: TimestampRendering) -> Settings {
+        Settings {
+            timestamp_rendering,
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn date_as_iso_string() {
+        let settings = date_settings(TimestampRendering::IsoStr);
+        assert_eq!(
+            date_to_value(&settings, 18628).unwrap(),
+            Value::String(String::from("2021-01-01"))
+        );
+        assert_eq!(
+            date_to_value(&settings, -1i32 as u32).unwrap(),
+            Value::String(String::from("1969-12-31"))
+        );
+    }
+
+    #[test]
+    fn date_before_unix_epoch_as_timestamp() {
+        let settings = date_settings(TimestampRendering::UnixMs);
+        assert_eq!(
+            date_to_value(&settings, -1i32 as u32).unwrap(),
+            Value::from(-86_400_000i64)
+        );
+        let settings = date_settings(TimestampRendering::Ticks);
+        assert_eq!(
+            date_to_value(&settings, -1i32 as u32).unwrap(),
+            Value::from(TICKS_TILL_UNIX_TIME as i64 - 864_000_000_000)
+        );
+    }
+
+    #[test]
+    fn explain_saturates_skipped_rows() {
+        let fixture = abc_fixture("explain-saturated-skip");
+        let settings = Settings {
+            resume_from: usize::MAX,
+            skip: 1,
+            ..Settings::default()
+        };
+        assert!(explain_files(&settings, &[fixture.path()], None).is_ok());
+    }
+
+    #[test]
+    fn progress_saturates_skipped_rows() {
+        let fixture = abc_fixture("progress-saturated-skip");
+        let settings = Settings {
+            resume_from: 1,
+            skip: usize::MAX,
+            progress: true,
+            ..Settings::default()
+        };
+        assert_eq!(fixture.convert(&settings).unwrap(), "");
+    }
 }
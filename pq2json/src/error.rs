@@ -0,0 +1,195 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use parquet::errors::ParquetError;
+
+/// Error returned by the conversion and metadata functions.
+///
+/// Errors of a particular row are reported as `Row`, with the underlying kind in its `source`.
+#[derive(Debug)]
+pub enum ConvertError {
+    /// Reading input or writing output failed.
+    Io(io::Error),
+    /// Parquet data or metadata couldn't be read.
+    ParquetRead(ParquetError),
+    /// Schemas of the input files don't match, or the schema doesn't support the settings.
+    Schema(String),
+    /// Map key type which can't be rendered as a JSON object key (see --lenient).
+    UnsupportedMapKey(String),
+    /// Decimal with invalid precision and scale.
+    InvalidDecimal { precision: i32, scale: i32 },
+    /// Requested column not present in the file.
+    MissingColumn(String),
+    /// File (path) is encrypted with Parquet modular encryption, which is not supported.
+    Encrypted(String),
+    /// Conversion of a row failed.
+    Row(RowError),
+    /// Any other failure, e.g. an invalid value or unsupported combination of settings.
+    Other(Box<dyn Error>),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConvertError::Io(e) => write!(f, "{}", e),
+            ConvertError::ParquetRead(e) => write!(f, "{}", e),
+            ConvertError::Schema(message) => write!(f, "{}", message),
+            ConvertError::UnsupportedMapKey(key_type) => {
+                write!(f, "Unsupported map key type: {}", key_type)
+            }
+            ConvertError::InvalidDecimal { precision, scale } => write!(
+                f,
+                "Invalid decimal metadata: precision {}, scale {}",
+                precision, scale
+            ),
+            ConvertError::MissingColumn(column) => write!(f, "Column not found: {}", column),
+            ConvertError::Encrypted(input_file) => write!(
+                f,
+                "{} is encrypted, decryption of Parquet files is not supported",
+                input_file
+            ),
+            ConvertError::Row(e) => write!(f, "{}", e),
+            ConvertError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ConvertError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConvertError::Io(e) => Some(e),
+            ConvertError::ParquetRead(e) => Some(e),
+            ConvertError::Row(e) => Some(e),
+            ConvertError::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConvertError {
+    fn from(e: io::Error) -> Self {
+        ConvertError::Io(e)
+    }
+}
+
+impl From<ParquetError> for ConvertError {
+    fn from(e: ParquetError) -> Self {
+        ConvertError::ParquetRead(e)
+    }
+}
+
+impl From<RowError> for ConvertError {
+    fn from(e: RowError) -> Self {
+        ConvertError::Row(e)
+    }
+}
+
+impl From<serde_json::Error> for ConvertError {
+    fn from(e: serde_json::Error) -> Self {
+        ConvertError::Other(e.into())
+    }
+}
+
+/// Recovers the error kind of errors passed along internally as `Box<dyn Error>`.
+impl From<Box<dyn Error>> for ConvertError {
+    fn from(e: Box<dyn Error>) -> Self {
+        let e = match e.downcast::<ConvertError>() {
+            Ok(e) => return *e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<RowError>() {
+            Ok(e) => return ConvertError::Row(*e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<io::Error>() {
+            Ok(e) => return ConvertError::Io(*e),
+            Err(e) => e,
+        };
+        match e.downcast::<ParquetError>() {
+            Ok(e) => ConvertError::ParquetRead(*e),
+            Err(e) => ConvertError::Other(e),
+        }
+    }
+}
+
+/// Error converting a top-level row.
+#[derive(Debug)]
+pub struct RowError {
+    /// Input file of the row, if read from a path.
+    pub file: Option<String>,
+    /// Zero-based index of the row in its file, counting every row of the file
+    /// (regardless of row group selection, filtering, sampling and --skip).
+    pub row: usize,
+    /// Top-level column which failed to convert, if known.
+    pub column: Option<String>,
+    pub source: Box<ConvertError>,
+}
+
+impl RowError {
+    /// Wraps the conversion error of the `row`-th row of the file, taking the column
+    /// from `ColumnError`.
+    pub(crate) fn new(file: Option<&str>, row: usize, error: Box<dyn Error>) -> Self {
+        let file = file.map(|file| file.to_owned());
+        match error.downcast::<ColumnError>() {
+            Ok(error) => RowError {
+                file,
+                row,
+                column: Some(error.column),
+                source: Box::new(error.source.into()),
+            },
+            Err(error) => RowError {
+                file,
+                row,
+                column: None,
+                source: Box::new(error.into()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}: ", file)?;
+        }
+        match self.column {
+            Some(ref column) => write!(f, "Row {}, column {}: {}", self.row, column, self.source),
+            None => write!(f, "Row {}: {}", self.row, self.source),
+        }
+    }
+}
+
+impl Error for RowError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Error converting a top-level column, turned into `RowError` by the row loop.
+#[derive(Debug)]
+pub(crate) struct ColumnError {
+    column: String,
+    source: Box<dyn Error>,
+}
+
+impl ColumnError {
+    pub(crate) fn new(column: &str, source: Box<dyn Error>) -> Self {
+        ColumnError {
+            column: column.to_owned(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for ColumnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Column {}: {}", self.column, self.source)
+    }
+}
+
+impl Error for ColumnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
@@ -0,0 +1,91 @@
+//! Parquet files crafted for tests.
+
+use std::env;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+
+use parquet::column::writer::{get_typed_column_writer_mut, ColumnWriter};
+use parquet::data_type::DataType;
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::{FileWriter, RowGroupWriter, SerializedFileWriter};
+use parquet::schema::parser::parse_message_type;
+
+use crate::converter::convert;
+use crate::error::ConvertError;
+use crate::settings::Settings;
+
+/// Parquet file in the temp directory, removed on drop.
+pub(crate) struct Fixture {
+    path: PathBuf,
+}
+
+impl Fixture {
+    /// Writes a file of the `message_type` schema with `row_groups` row groups,
+    /// `write_column` is called with the row group index, leaf column index and writer
+    /// of every column chunk. `name` must be unique across tests.
+    pub(crate) fn new<F>(
+        name: &str,
+        message_type: &str,
+        row_groups: usize,
+        mut write_column: F,
+    ) -> Self
+    where
+        F: FnMut(usize, usize, &mut ColumnWriter),
+    {
+        let path = env::temp_dir().join(format!("pq2json-{}-{}.parquet", process::id(), name));
+        let schema = Arc::new(parse_message_type(message_type).unwrap());
+        let properties = Arc::new(WriterProperties::builder().build());
+        let file = File::create(&path).unwrap();
+        let mut writer = SerializedFileWriter::new(file, schema, properties).unwrap();
+        for row_group in 0..row_groups {
+            let mut row_group_writer = writer.next_row_group().unwrap();
+            let mut column = 0;
+            while let Some(mut column_writer) = row_group_writer.next_column().unwrap() {
+                write_column(row_group, column, &mut column_writer);
+                row_group_writer.close_column(column_writer).unwrap();
+                column += 1;
+            }
+            writer.close_row_group(row_group_writer).unwrap();
+        }
+        writer.close().unwrap();
+        Fixture { path }
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        self.path.to_str().unwrap()
+    }
+
+    pub(crate) fn reader(&self) -> Box<dyn FileReader> {
+        Box::new(SerializedFileReader::new(File::open(&self.path).unwrap()).unwrap())
+    }
+
+    /// Converts the file with `convert` and returns the output.
+    pub(crate) fn convert(&self, settings: &Settings) -> Result<String, ConvertError> {
+        let output_path = self.path.with_extension("out");
+        let result = convert(settings, self.path(), output_path.to_str());
+        let output = fs::read_to_string(&output_path);
+        let _ = fs::remove_file(&output_path);
+        result.map(|_| output.unwrap())
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Writes a batch of values to the column writer of `T` values.
+pub(crate) fn write_batch<T: DataType>(
+    column_writer: &mut ColumnWriter,
+    values: &[T::T],
+    def_levels: Option<&[i16]>,
+    rep_levels: Option<&[i16]>,
+) {
+    get_typed_column_writer_mut::<T>(column_writer)
+        .write_batch(values, def_levels, rep_levels)
+        .unwrap();
+}
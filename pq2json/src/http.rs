@@ -0,0 +1,71 @@
+use std::error::Error;
+use std::io::{Cursor, Read};
+
+use log::debug;
+use parquet::errors::{ParquetError, Result as ParquetResult};
+use parquet::file::reader::{ChunkReader, Length};
+
+/// Chunk reader over a file served over HTTP(S), every chunk is fetched with a range request,
+/// so that only the footer and the projected column chunks are downloaded.
+pub(crate) struct HttpReader {
+    url: String,
+    len: u64,
+    /// Headers sent with every request, e.g. authorization.
+    headers: Vec<(String, String)>,
+}
+
+impl HttpReader {
+    /// Resolves the file length with a HEAD request.
+    pub(crate) fn new(url: &str, headers: Vec<(String, String)>) -> Result<Self, Box<dyn Error>> {
+        let response = with_headers(ureq::head(url), &headers).call()?;
+        let len = response
+            .header("Content-Length")
+            .and_then(|len| len.parse::<u64>().ok())
+            .ok_or_else(|| format!("Missing Content-Length of {}", url))?;
+        Ok(HttpReader {
+            url: url.to_owned(),
+            len,
+            headers,
+        })
+    }
+
+    fn fetch(&self, start: u64, length: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let end = (start + length as u64).min(self.len);
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        let mut buf = Vec::with_capacity((end - start) as usize);
+        debug!("Fetching bytes {}-{} of {}", start, end - 1, self.url);
+        let response = with_headers(ureq::get(&self.url), &self.headers)
+            .set("Range", &format!("bytes={}-{}", start, end - 1))
+            .call()?;
+        // Servers ignoring the range respond with the whole file.
+        if response.status() != 206 {
+            return Err(format!("{} doesn't support range requests", self.url).into());
+        }
+        response.into_reader().read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+fn with_headers(request: ureq::Request, headers: &[(String, String)]) -> ureq::Request {
+    headers
+        .iter()
+        .fold(request, |request, (name, value)| request.set(name, value))
+}
+
+impl Length for HttpReader {
+    fn len(&self) -> u64 {
+        self.len
+    }
+}
+
+impl ChunkReader for HttpReader {
+    type T = Cursor<Vec<u8>>;
+
+    fn get_read(&self, start: u64, length: usize) -> ParquetResult<Cursor<Vec<u8>>> {
+        self.fetch(start, length)
+            .map(Cursor::new)
+            .map_err(|e| ParquetError::General(e.to_string()))
+    }
+}
@@ -0,0 +1,386 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+#[cfg(feature = "mmap")]
+use std::sync::Arc;
+
+use log::{debug, info, warn};
+use parquet::errors::Result as ParquetResult;
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::reader::{
+    ChunkReader, FileReader, Length, RowGroupReader, SerializedFileReader,
+};
+use parquet::record::reader::RowIter;
+use parquet::schema::types::Type as SchemaType;
+use parquet::util::cursor::SliceableCursor;
+
+use crate::error::ConvertError;
+#[cfg(feature = "http")]
+use crate::http::HttpReader;
+use crate::settings::Settings;
+
+/// Input path denoting STDIN.
+pub const STDIN_INPUT: &str = "-";
+
+/// Trailing magic of files written with Parquet modular encryption in encrypted footer mode.
+const ENCRYPTED_MAGIC: &[u8; 4] = b"PARE";
+
+/// Opens Parquet file reader.
+///
+/// Only the footer (file metadata) is read on opening, data pages are read as rows are
+/// requested. So schema and metadata dumps read a few kilobytes regardless of the file size,
+/// also over HTTP, except for STDIN which is buffered entirely.
+///
+/// Arguments:
+///
+/// * `input_file` - Parquet file path, HTTP(S) URL, or `-` to read the whole file
+///   from STDIN into memory
+///
+pub fn open_reader(input_file: &str) -> Result<Box<dyn FileReader>, Box<dyn Error>> {
+    info!("Opening {}", input_file);
+    if is_url(input_file) {
+        open_http_reader(input_file)
+    } else if input_file == STDIN_INPUT {
+        // Parquet reader needs random access (footer first), so STDIN is buffered entirely.
+        let mut buf = Vec::new();
+        io::stdin().read_to_end(&mut buf)?;
+        new_file_reader(input_file, SliceableCursor::new(buf))
+    } else {
+        let file = File::open(&Path::new(input_file))?;
+        new_file_reader(input_file, file)
+    }
+}
+
+/// Whether the input is an HTTP(S) URL rather than a file path.
+pub(crate) fn is_url(input_file: &str) -> bool {
+    input_file.starts_with("http://") || input_file.starts_with("https://")
+}
+
+#[cfg(feature = "http")]
+fn open_http_reader(url: &str) -> Result<Box<dyn FileReader>, Box<dyn Error>> {
+    #[cfg(feature = "azure")]
+    let headers = crate::azure::blob_headers(url)?;
+    #[cfg(not(feature = "azure"))]
+    let headers = Vec::new();
+    new_file_reader(url, HttpReader::new(url, headers)?)
+}
+
+#[cfg(not(feature = "http"))]
+fn open_http_reader(_url: &str) -> Result<Box<dyn FileReader>, Box<dyn Error>> {
+    Err("Reading URLs requires pq2json built with the http feature".into())
+}
+
+/// Creates file reader, failing with a clear error for encrypted files, which
+/// the Parquet reader can't decrypt (and would report as corrupt).
+///
+/// Files with plaintext footer (`PAR1` magic) and encrypted columns can't be told apart
+/// from corrupt files, since the footer encryption fields are not decoded by the reader.
+fn new_file_reader<R: ChunkReader + 'static>(
+    input_file: &str,
+    chunk_reader: R,
+) -> Result<Box<dyn FileReader>, Box<dyn Error>> {
+    if has_encrypted_footer(&chunk_reader) {
+        return Err(ConvertError::Encrypted(input_file.to_owned()).into());
+    }
+    Ok(Box::new(SerializedFileReader::new(chunk_reader)?))
+}
+
+fn has_encrypted_footer<R: ChunkReader>(chunk_reader: &R) -> bool {
+    let len = chunk_reader.len();
+    if len < ENCRYPTED_MAGIC.len() as u64 {
+        return false;
+    }
+    let mut magic = [0u8; 4];
+    match chunk_reader.get_read(len - magic.len() as u64, magic.len()) {
+        Ok(mut read) => read.read_exact(&mut magic).is_ok() && magic == *ENCRYPTED_MAGIC,
+        Err(_) => false,
+    }
+}
+
+/// Opens Parquet file reader for conversion, memory-mapping the file with --mmap
+/// (STDIN is always read into memory, URLs are always read with range requests).
+pub(crate) fn open_input(
+    settings: &Settings,
+    input_file: &str,
+) -> Result<Box<dyn FileReader>, Box<dyn Error>> {
+    if settings.mmap && input_file != STDIN_INPUT && !is_url(input_file) {
+        info!("Memory-mapping {}", input_file);
+        open_mmap_reader(input_file)
+    } else {
+        open_reader(input_file)
+    }
+}
+
+#[cfg(feature = "mmap")]
+fn open_mmap_reader(input_file: &str) -> Result<Box<dyn FileReader>, Box<dyn Error>> {
+    let file = File::open(&Path::new(input_file))?;
+    // Safety: the file must not be modified while it's mapped, same as it must not be
+    // modified while being read with regular reads for the output to be consistent.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    new_file_reader(
+        input_file,
+        MmapReader {
+            mmap: Arc::new(mmap),
+        },
+    )
+}
+
+#[cfg(not(feature = "mmap"))]
+fn open_mmap_reader(_input_file: &str) -> Result<Box<dyn FileReader>, Box<dyn Error>> {
+    Err("--mmap requires pq2json built with the mmap feature".into())
+}
+
+/// Chunk reader over a memory-mapped file, chunks are read without copying the file.
+#[cfg(feature = "mmap")]
+struct MmapReader {
+    mmap: Arc<memmap2::Mmap>,
+}
+
+#[cfg(feature = "mmap")]
+impl Length for MmapReader {
+    fn len(&self) -> u64 {
+        self.mmap.len() as u64
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl ChunkReader for MmapReader {
+    type T = MmapChunk;
+
+    fn get_read(&self, start: u64, length: usize) -> ParquetResult<MmapChunk> {
+        let start = start as usize;
+        let end = start.saturating_add(length).min(self.mmap.len());
+        Ok(MmapChunk {
+            mmap: self.mmap.clone(),
+            pos: start.min(end),
+            end,
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+struct MmapChunk {
+    mmap: Arc<memmap2::Mmap>,
+    pos: usize,
+    end: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl Read for MmapChunk {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.end - self.pos);
+        buf[..len].copy_from_slice(&self.mmap[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Verifies that schemas of all files match the schema of the first one,
+/// mismatches are only logged if `ignore_mismatch` is set.
+pub(crate) fn check_schemas(
+    input_files: &[&str],
+    readers: &[Box<dyn FileReader>],
+    ignore_mismatch: bool,
+) -> Result<(), Box<dyn Error>> {
+    let schema_fields = |reader: &dyn FileReader| {
+        reader
+            .metadata()
+            .file_metadata()
+            .schema()
+            .get_fields()
+            .to_vec()
+    };
+    let expected = match readers.first() {
+        Some(reader) => schema_fields(reader.as_ref()),
+        None => return Ok(()),
+    };
+    for (input_file, reader) in input_files.iter().zip(readers).skip(1) {
+        if schema_fields(reader.as_ref()) != expected {
+            if !ignore_mismatch {
+                return Err(ConvertError::Schema(format!(
+                    "Schema of {} doesn't match the schema of {}",
+                    input_file, input_files[0]
+                ))
+                .into());
+            }
+            warn!(
+                "Schema of {} doesn't match the schema of {}",
+                input_file, input_files[0]
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Restricts file reader to the specified row groups (in the given order).
+pub(crate) fn select_row_groups(
+    reader: Box<dyn FileReader>,
+    indices: &[usize],
+) -> Result<Box<dyn FileReader>, Box<dyn Error>> {
+    let num_row_groups = reader.num_row_groups();
+    if let Some(i) = indices.iter().find(|&&i| i >= num_row_groups) {
+        return Err(format!(
+            "Row group index {} is out of range, the file has {} row groups",
+            i, num_row_groups
+        )
+        .into());
+    }
+    Ok(Box::new(RowGroupsReader {
+        reader,
+        indices: indices.to_vec(),
+    }))
+}
+
+/// File reader exposing a subset of row groups of the underlying reader.
+struct RowGroupsReader {
+    reader: Box<dyn FileReader>,
+    indices: Vec<usize>,
+}
+
+impl FileReader for RowGroupsReader {
+    fn metadata(&self) -> &ParquetMetaData {
+        self.reader.metadata()
+    }
+
+    fn num_row_groups(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn get_row_group(&self, i: usize) -> ParquetResult<Box<dyn RowGroupReader + '_>> {
+        let row_group_reader = self.reader.get_row_group(self.indices[i])?;
+        debug!(
+            "Reading row group {} ({} rows)",
+            self.indices[i],
+            row_group_reader.metadata().num_rows()
+        );
+        Ok(row_group_reader)
+    }
+
+    fn get_row_iter(&self, projection: Option<SchemaType>) -> ParquetResult<RowIter> {
+        RowIter::from_file(projection, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{write_batch, Fixture};
+    use parquet::data_type::Int64Type;
+    #[cfg(feature = "mmap")]
+    use std::env;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    #[cfg(feature = "mmap")]
+    use std::time::Instant;
+
+    /// Chunk reader over file bytes, recording the ranges read.
+    struct RecordingReader {
+        cursor: SliceableCursor,
+        reads: Arc<Mutex<Vec<(u64, usize)>>>,
+    }
+
+    impl Length for RecordingReader {
+        fn len(&self) -> u64 {
+            self.cursor.len()
+        }
+    }
+
+    impl ChunkReader for RecordingReader {
+        type T = SliceableCursor;
+
+        fn get_read(&self, start: u64, length: usize) -> ParquetResult<SliceableCursor> {
+            self.reads.lock().unwrap().push((start, length));
+            self.cursor.get_read(start, length)
+        }
+    }
+
+    #[test]
+    fn opening_reads_only_the_footer() {
+        let fixture = Fixture::new(
+            "footer-only",
+            "message schema {
+                REQUIRED INT64 id;
+            }",
+            1,
+            |_, _, writer| {
+                let values = (0..100_000).collect::<Vec<i64>>();
+                write_batch::<Int64Type>(writer, &values, None, None);
+            },
+        );
+        let bytes = fs::read(fixture.path()).unwrap();
+        let len = bytes.len() as u64;
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let chunk_reader = RecordingReader {
+            cursor: SliceableCursor::new(bytes),
+            reads: reads.clone(),
+        };
+        let reader = new_file_reader(fixture.path(), chunk_reader).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 100_000);
+        assert_eq!(
+            reader
+                .metadata()
+                .file_metadata()
+                .schema()
+                .get_fields()
+                .len(),
+            1
+        );
+        // The footer may be read ahead of its length, by up to 64 KiB from the end.
+        let footer_start = len - 64 * 1024;
+        let reads = reads.lock().unwrap();
+        assert!(!reads.is_empty());
+        assert!(
+            reads.iter().all(|(start, _)| *start >= footer_start),
+            "{:?} of {} bytes",
+            reads,
+            len
+        );
+    }
+
+    /// Timing of reading all rows with regular reads versus --mmap, see "Timing harnesses"
+    /// in the README. Reads the file given by `PQ2JSON_TIMING_INPUT`, or a generated one.
+    #[cfg(feature = "mmap")]
+    #[test]
+    #[ignore]
+    fn mmap_timing() {
+        let fixture;
+        let input_file = match env::var("PQ2JSON_TIMING_INPUT") {
+            Ok(input_file) => input_file,
+            Err(_) => {
+                fixture = Fixture::new(
+                    "mmap-timing",
+                    "message schema {
+                        REQUIRED INT64 id;
+                        REQUIRED INT64 value;
+                    }",
+                    10,
+                    |row_group, column, writer| {
+                        let values = (0..1_000_000)
+                            .map(|i| (row_group * 1_000_000 + i) as i64 * (column as i64 + 1))
+                            .collect::<Vec<_>>();
+                        write_batch::<Int64Type>(writer, &values, None, None);
+                    },
+                );
+                fixture.path().to_owned()
+            }
+        };
+        let read_rows = |mmap: bool| {
+            let settings = Settings {
+                mmap,
+                ..Settings::default()
+            };
+            let start = Instant::now();
+            let reader = open_input(&settings, &input_file).unwrap();
+            let rows = reader.get_row_iter(None).unwrap().count();
+            (rows, start.elapsed())
+        };
+        let (rows, read_elapsed) = read_rows(false);
+        let (mmap_rows, mmap_elapsed) = read_rows(true);
+        assert_eq!(rows, mmap_rows);
+        println!(
+            "{} rows of {}: regular reads {:?}, --mmap {:?}",
+            rows, input_file, read_elapsed, mmap_elapsed
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use std::error::Error;
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatchReader;
+use itertools::Itertools;
+use log::{info, warn};
+use parquet::arrow::{ArrowReader, ParquetFileArrowReader};
+use parquet::file::reader::FileReader;
+
+use crate::error::ConvertError;
+use crate::input::select_row_groups;
+use crate::settings::{BytesEncoding, Settings, TimestampRendering};
+
+/// Number of rows per Arrow record batch.
+const ARROW_BATCH_SIZE: usize = 64 * 1024;
+
+/// Writes Parquet data as an Arrow IPC stream, reading it column-wise with the Arrow reader
+/// (no per-row JSON values). Nested structs and lists are kept as Arrow nested types.
+///
+/// Only row group selection and top-level or dotted --columns projection apply,
+/// row-level options (filtering, sampling, limits, value rendering), column aliases,
+/// GUID rendering and synthetic columns are not supported.
+///
+/// Multiple readers are written as a single stream, their Arrow schemas must be identical.
+pub(crate) fn write_arrow_stream(
+    settings: &Settings,
+    readers: Vec<Box<dyn FileReader>>,
+    writer: Box<dyn Write>,
+) -> Result<(), Box<dyn Error>> {
+    check_arrow_settings(settings)?;
+
+    let mut writer = Some(writer);
+    // Stream is started with the schema of the first file.
+    let mut stream: Option<(StreamWriter<Box<dyn Write>>, SchemaRef)> = None;
+    let mut rows_written = 0usize;
+    for reader in readers {
+        let leaf_columns = match settings.columns {
+            Some(ref columns) => Some(leaf_column_indices(settings, reader.as_ref(), columns)?),
+            None => None,
+        };
+        let reader = match settings.row_groups {
+            Some(ref indices) => select_row_groups(reader, indices)?,
+            None => reader,
+        };
+
+        let mut arrow_reader = ParquetFileArrowReader::new(Arc::from(reader));
+        let batches = match leaf_columns {
+            Some(leaf_columns) => {
+                arrow_reader.get_record_reader_by_columns(leaf_columns, ARROW_BATCH_SIZE)?
+            }
+            None => arrow_reader.get_record_reader(ARROW_BATCH_SIZE)?,
+        };
+
+        let schema = batches.schema();
+        if stream.is_none() {
+            let writer = writer.take().expect("writer is taken once");
+            stream = Some((StreamWriter::try_new(writer, &schema)?, schema.clone()));
+        }
+        let (stream_writer, stream_schema) = stream.as_mut().unwrap();
+        if *stream_schema != schema {
+            return Err(ConvertError::Schema(
+                "Arrow schemas of the input files don't match".to_owned(),
+            )
+            .into());
+        }
+        for batch in batches {
+            let batch = batch?;
+            stream_writer.write(&batch)?;
+            rows_written += batch.num_rows();
+        }
+    }
+    if let Some((mut stream_writer, _)) = stream {
+        stream_writer.finish()?;
+    }
+    info!("{} rows written", rows_written);
+    Ok(())
+}
+
+/// Arrow batches are read as is, so options selecting rows, adding columns or rendering
+/// values as JSON don't apply.
+fn check_arrow_settings(settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let unsupported = [
+        ("--exclude-columns", settings.exclude_columns.is_some()),
+        ("--columns-regex", settings.columns_regex.is_some()),
+        ("--limit", settings.limit.is_some()),
+        ("--skip", settings.skip > 0),
+        ("--tail", settings.tail.is_some()),
+        ("--resume-from", settings.resume_from > 0),
+        ("--where", settings.filter.is_some()),
+        ("--sample", settings.sample.is_some()),
+        ("--explode", settings.explode.is_some()),
+        ("--flatten", settings.flatten),
+        ("--transform", !settings.transforms.is_empty()),
+        ("--split-rows", settings.split_rows.is_some()),
+        ("--split-bytes", settings.split_bytes.is_some()),
+        ("--columns aliases", !settings.column_aliases.is_empty()),
+        ("--uuid-columns", !settings.uuid_columns.is_empty()),
+        ("--add-rownum-column", settings.rownum_column.is_some()),
+        ("--add-filename-column", settings.filename_column.is_some()),
+        ("--omit-nulls", settings.omit_nulls),
+        ("--keep-nulls", !settings.keep_nulls.is_empty()),
+        ("--skip-empty-rows", settings.skip_empty_rows),
+        (
+            "--timestamp",
+            settings.timestamp_rendering != TimestampRendering::Ticks,
+        ),
+        ("--timestamp-format", settings.timestamp_format.is_some()),
+        ("--timezone", settings.timezone.is_some()),
+        (
+            "--bytes-encoding",
+            settings.bytes_encoding != BytesEncoding::Array,
+        ),
+        ("--bytes-as-string", settings.bytes_as_string),
+        ("--decimal-as-number", settings.decimal_as_number),
+        ("--long-as-string", settings.long_as_string),
+        ("--pretty", settings.pretty),
+    ];
+    match unsupported.iter().find(|(_, present)| *present) {
+        Some((option, _)) => Err(format!("{} is not supported with --format arrow", option).into()),
+        None => Ok(()),
+    }
+}
+
+/// Resolves --columns (top-level names or dotted nested paths) into the indices
+/// of the leaf columns they cover. Columns not in the file are skipped with a warning,
+/// or fail with --strict-columns.
+fn leaf_column_indices(
+    settings: &Settings,
+    reader: &dyn FileReader,
+    columns: &[String],
+) -> Result<Vec<usize>, Box<dyn Error>> {
+    let schema_descr = reader.metadata().file_metadata().schema_descr();
+    let paths = schema_descr
+        .columns()
+        .iter()
+        .map(|column| column.path().parts().join("."))
+        .collect::<Vec<_>>();
+
+    let mut indices = Vec::new();
+    let mut missing_columns = Vec::new();
+    for column in columns {
+        let prefix = format!("{}.", column);
+        let matching = paths
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| *path == column || path.starts_with(&prefix))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            missing_columns.push(column);
+        }
+        indices.extend(matching);
+    }
+    if !missing_columns.is_empty() {
+        let missing = missing_columns.iter().join(", ");
+        if settings.strict_columns {
+            return Err(ConvertError::MissingColumn(missing).into());
+        }
+        warn!("Columns not found in the file: {}", missing);
+    }
+    indices.sort_unstable();
+    indices.dedup();
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{write_batch, Fixture};
+    use parquet::data_type::Int32Type;
+    use std::collections::HashMap;
+
+    fn ab_fixture(name: &str) -> Fixture {
+        Fixture::new(
+            name,
+            "message schema {
+                REQUIRED INT32 a;
+                REQUIRED GROUP b {
+                    REQUIRED INT32 x;
+                    REQUIRED INT32 y;
+                }
+            }",
+            1,
+            |_, column, writer| write_batch::<Int32Type>(writer, &[column as i32], None, None),
+        )
+    }
+
+    fn unsupported_option(settings: &Settings) -> String {
+        check_arrow_settings(settings).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn rejects_column_aliases() {
+        let mut column_aliases = HashMap::new();
+        column_aliases.insert(String::from("a"), String::from("b"));
+        let settings = Settings {
+            column_aliases,
+            ..Settings::default()
+        };
+        assert_eq!(
+            unsupported_option(&settings),
+            "--columns aliases is not supported with --format arrow"
+        );
+    }
+
+    #[test]
+    fn rejects_uuid_columns() {
+        let settings = Settings {
+            uuid_columns: vec![String::from("a")],
+            ..Settings::default()
+        };
+        assert_eq!(
+            unsupported_option(&settings),
+            "--uuid-columns is not supported with --format arrow"
+        );
+    }
+
+    #[test]
+    fn rejects_synthetic_columns() {
+        let settings = Settings {
+            rownum_column: Some(String::from("rownum")),
+            ..Settings::default()
+        };
+        assert_eq!(
+            unsupported_option(&settings),
+            "--add-rownum-column is not supported with --format arrow"
+        );
+        let settings = Settings {
+            filename_column: Some(String::from("filename")),
+            ..Settings::default()
+        };
+        assert_eq!(
+            unsupported_option(&settings),
+            "--add-filename-column is not supported with --format arrow"
+        );
+    }
+
+    #[test]
+    fn rejects_value_rendering_options() {
+        let settings = Settings {
+            omit_nulls: true,
+            ..Settings::default()
+        };
+        assert_eq!(
+            unsupported_option(&settings),
+            "--omit-nulls is not supported with --format arrow"
+        );
+        let settings = Settings {
+            timestamp_rendering: TimestampRendering::IsoStr,
+            ..Settings::default()
+        };
+        assert_eq!(
+            unsupported_option(&settings),
+            "--timestamp is not supported with --format arrow"
+        );
+        let settings = Settings {
+            bytes_as_string: true,
+            ..Settings::default()
+        };
+        assert_eq!(
+            unsupported_option(&settings),
+            "--bytes-as-string is not supported with --format arrow"
+        );
+        let settings = Settings {
+            pretty: true,
+            ..Settings::default()
+        };
+        assert_eq!(
+            unsupported_option(&settings),
+            "--pretty is not supported with --format arrow"
+        );
+    }
+
+    #[test]
+    fn columns_resolve_to_leaf_columns() {
+        let fixture = ab_fixture("arrow-leaf-columns");
+        let reader = fixture.reader();
+        let columns = [String::from("b"), String::from("a")];
+        assert_eq!(
+            leaf_column_indices(&Settings::default(), reader.as_ref(), &columns).unwrap(),
+            vec![0, 1, 2]
+        );
+        let columns = [String::from("b.y")];
+        assert_eq!(
+            leaf_column_indices(&Settings::default(), reader.as_ref(), &columns).unwrap(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn missing_columns_are_skipped() {
+        let fixture = ab_fixture("arrow-missing-columns");
+        let reader = fixture.reader();
+        let columns = [String::from("a"), String::from("c"), String::from("b.z")];
+        assert_eq!(
+            leaf_column_indices(&Settings::default(), reader.as_ref(), &columns).unwrap(),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn missing_columns_fail_with_strict_columns() {
+        let fixture = ab_fixture("arrow-strict-columns");
+        let reader = fixture.reader();
+        let settings = Settings {
+            strict_columns: true,
+            ..Settings::default()
+        };
+        let columns = [String::from("a"), String::from("c"), String::from("b.z")];
+        let error = leaf_column_indices(&settings, reader.as_ref(), &columns).unwrap_err();
+        match ConvertError::from(error) {
+            ConvertError::MissingColumn(missing) => assert_eq!(missing, "c, b.z"),
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Parquet to JSON (line delimited), CSV and Arrow IPC conversion.
+//!
+//! Conversion and metadata functions fail with `ConvertError`, whose variants
+//! distinguish IO, Parquet, schema and value conversion failures.
+
+#[cfg(feature = "azure")]
+mod azure;
+mod converter;
+mod error;
+#[cfg(test)]
+mod fixtures;
+#[cfg(feature = "http")]
+mod http;
+mod input;
+mod ipc;
+mod output;
+mod parallel;
+mod progress;
+mod schema;
+mod settings;
+
+pub use crate::converter::{
+    convert, convert_files, convert_from, explain_files, for_each_row, rows, validate_files,
+};
+pub use crate::error::{ConvertError, RowError};
+pub use crate::schema::{
+    print_create_table, print_csl_schema, print_json_schema, print_null_report,
+    print_record_json_schema, print_row_count, print_row_groups_metadata, print_schema,
+    print_statistics,
+};
+pub use crate::settings::{
+    BytesEncoding, CsvQuote, CsvTerminator, DuplicateKey, ExplodeEmpty, FilterOp, FloatSpecial,
+    Newline, OutputEncoding, OutputFormat, OverflowHandling, PreEpoch, RowFilter, Settings,
+    TimestampRendering, Transform,
+};
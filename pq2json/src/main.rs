@@ -1,14 +1,27 @@
-use clap::{App, Arg};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::process;
 
-use crate::settings::{Settings, TimestampRendering};
+use chrono_tz::Tz;
+use clap::{App, Arg};
+use log::LevelFilter;
+use regex::Regex;
 
-mod converter;
-mod schema;
-mod settings;
+use pq2json::{
+    BytesEncoding, CsvQuote, CsvTerminator, DuplicateKey, ExplodeEmpty, FloatSpecial, Newline,
+    OutputEncoding, OutputFormat, OverflowHandling, PreEpoch, RowFilter, Settings,
+    TimestampRendering, Transform,
+};
 
 fn main() {
     let matches = App::new("pq2json")
         .version("0.1")
+        .after_help(
+            "TIME (time of day) columns are rendered as HH:MM:SS.fff strings and JSON \
+             columns are embedded as parsed JSON only at the top level. TIME values nested \
+             in structs, lists and maps are written as numbers of milli/microseconds since \
+             midnight, and nested JSON values as strings.",
+        )
         .arg(
             Arg::with_name("omit-nulls")
                 .long("omit-nulls")
@@ -16,6 +29,16 @@ fn main() {
                 .takes_value(false)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("keep-nulls")
+                .long("keep-nulls")
+                .help(
+                    "Top-level columns whose nulls are kept despite --omit-nulls, \
+                     either comma separated or as a JSON array of column names",
+                )
+                .takes_value(true)
+                .required(false),
+        )
         .arg(
             Arg::with_name("omit-empty-bags")
                 .long("omit-empty-bags")
@@ -23,6 +46,16 @@ fn main() {
                 .takes_value(false)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("skip-empty-rows")
+                .long("skip-empty-rows")
+                .help(
+                    "Don't write JSON records of rows without any values left \
+                     (e.g. with --omit-nulls and --omit-empty-bags), instead of empty bags",
+                )
+                .takes_value(false)
+                .required(false),
+        )
         .arg(
             Arg::with_name("omit-empty-lists")
                 .long("omit-empty-lists")
@@ -30,11 +63,96 @@ fn main() {
                 .takes_value(false)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("empty-string-as-null")
+                .long("empty-string-as-null")
+                .help(
+                    "Treat empty strings as nulls (so that --omit-nulls drops them), \
+                     other types are not affected",
+                )
+                .takes_value(false)
+                .required(false),
+        )
         .arg(
             Arg::with_name("convert-types")
                 .short("r")
                 .long("convert-types")
-                .help("Implicit Parquet to Kusto types conversion (e.g. U64 into long)")
+                .help(
+                    "Implicit Parquet to Kusto types conversion \
+                     (unsigned integers into int or long, see --on-overflow)",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("on-overflow")
+                .long("on-overflow")
+                .possible_values(&["keep", "string", "error"])
+                .default_value("keep")
+                .help(
+                    "Handling of unsigned values above the signed maximum under --convert-types. \
+                     Either keep (unsigned number), string (JSON string) or error (fail)",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("float-special")
+                .long("float-special")
+                .possible_values(&["null", "string"])
+                .default_value("null")
+                .help(
+                    "Rendering of NaN and infinite floats. Either \
+                     null or string (\"NaN\", \"Infinity\", \"-Infinity\")",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("decimal-as-number")
+                .long("decimal-as-number")
+                .help(
+                    "Render decimals as JSON numbers when this is lossless \
+                     (otherwise decimals are rendered as strings)",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("long-as-string")
+                .long("long-as-string")
+                .help(
+                    "Render 64-bit integers outside of +-(2^53 - 1) (the range exactly \
+                     representable as doubles, e.g. in JavaScript) as JSON strings",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("max-depth")
+                .long("max-depth")
+                .value_name("N")
+                .default_value("100")
+                .help("Maximum nesting depth of struct, list and map values")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("on-duplicate-key")
+                .long("on-duplicate-key")
+                .possible_values(&["last", "first", "error", "array"])
+                .default_value("last")
+                .help(
+                    "Map entries with a repeated key: keep the last or the first value, \
+                     fail, or collect the values into an array",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("lenient")
+                .long("lenient")
+                .help("Stringify unsupported map keys (e.g. floats or binary) instead of failing")
                 .takes_value(false)
                 .required(false),
         )
@@ -49,13 +167,265 @@ fn main() {
                 .takes_value(false)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("pretty")
+                .long("pretty")
+                .help(
+                    "Pretty-print JSON records with indentation \
+                     (records span multiple lines, so the output is no longer valid JSONL)",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("json-array")
+                .long("json-array")
+                .help("Deprecated, same as --format json-array")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("flatten")
+                .long("flatten")
+                .help(
+                    "Promote nested struct fields to top-level fields named by their dotted path \
+                     (lists and maps are kept as JSON values)",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("flatten-separator")
+                .long("flatten-separator")
+                .value_name("SEP")
+                .default_value(".")
+                .help("Separator between nested field names with --flatten")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("explode")
+                .long("explode")
+                .value_name("COLUMN")
+                .help(
+                    "Emit one row per element of the specified top-level list column, \
+                     repeating the other columns",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("explode-empty")
+                .long("explode-empty")
+                .possible_values(&["drop", "null"])
+                .default_value("drop")
+                .help(
+                    "With --explode, either drop rows with empty or null lists, \
+                     or emit them once with a null element",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .possible_values(&[
+                    "jsonl",
+                    "csv",
+                    "tsv",
+                    "json-array",
+                    "arrow",
+                    "kusto-multijson",
+                ])
+                .default_value("jsonl")
+                .help(
+                    "Output format: jsonl, csv or tsv (root level fields, \
+                     nested structures formatted as JSON strings, TSV fields \
+                     backslash-escaped instead of quoted), \
+                     json-array (single JSON array of records, indented with --pretty), \
+                     arrow (Arrow IPC stream, preserving nested types) \
+                     or kusto-multijson (JSON records for Kusto multijson ingestion)",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("mapping-out")
+                .long("mapping-out")
+                .value_name("PATH")
+                .help(
+                    "Write Kusto JSON ingestion mapping of the output columns \
+                     (with their inferred Kusto types) to the file",
+                )
+                .takes_value(true)
+                .required(false),
+        )
         .arg(
             Arg::with_name("csv")
                 .long("csv")
-                .help("Output root level fields in CSV format")
+                .help("Deprecated, same as --format csv")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("split-rows")
+                .long("split-rows")
+                .value_name("N")
+                .help(
+                    "Split output into files of at most N rows each, \
+                     named after --output with a part index (e.g. out.0.jsonl, out.1.jsonl)",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("split-bytes")
+                .long("split-bytes")
+                .value_name("SIZE")
+                .help(
+                    "Split output into files of approximately at most SIZE bytes \
+                     (before compression) each, with K, M or G suffix (e.g. 100M), \
+                     named same as with --split-rows",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("per-row-group")
+                .long("per-row-group")
+                .help(
+                    "Write every row group to its own file named after --output \
+                     (e.g. out.rg0.jsonl, out.rg1.jsonl), numbered across the input files",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("buffer-size")
+                .long("buffer-size")
+                .value_name("SIZE")
+                .help("Output buffer size, with K, M or G suffix (256K by default)")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("gzip")
+                .long("gzip")
+                .help("Compress the output with gzip")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("append")
+                .long("append")
+                .help(
+                    "Append to the output file instead of overwriting it \
+                     (the CSV header is only written to an empty file)",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("checksum")
+                .long("checksum")
+                .help(
+                    "Print SHA-256 of the output (of every part when split) to STDERR, \
+                     in sha256sum format",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("checksum-out")
+                .long("checksum-out")
+                .value_name("PATH")
+                .help("Write the --checksum lines to the file instead, implies --checksum")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("gzip-level")
+                .long("gzip-level")
+                .value_name("LEVEL")
+                .help("Gzip compression level (0-9), implies --gzip")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("csv-header")
+                .long("csv-header")
+                .help("Write a CSV header row with column names")
                 .takes_value(false)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("csv-null")
+                .long("csv-null")
+                .value_name("TEXT")
+                .help("Text to write for null values in CSV output (empty by default)")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("newline")
+                .long("newline")
+                .possible_values(&["lf", "crlf"])
+                .default_value("lf")
+                .help("Line terminator of JSON output (see --csv-terminator for CSV)")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("csv-terminator")
+                .long("csv-terminator")
+                .possible_values(&["lf", "crlf", "cr"])
+                .default_value("lf")
+                .help("CSV record terminator")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("csv-quote")
+                .long("csv-quote")
+                .possible_values(&["always", "necessary", "non-numeric", "never"])
+                .default_value("necessary")
+                .help(
+                    "CSV field quoting: always, necessary (fields with delimiters, \
+                     quotes or line breaks), non-numeric or never",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("output-encoding")
+                .long("output-encoding")
+                .possible_values(&["utf-8", "utf-16le"])
+                .default_value("utf-8")
+                .help(
+                    "Character encoding of csv and tsv output, utf-16le output starts \
+                     with a byte order mark",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("bool-as-int")
+                .long("bool-as-int")
+                .help(
+                    "Render booleans as 1 and 0 in csv and tsv output \
+                     (nested values formatted as JSON still use true and false)",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("delimiter")
+                .long("delimiter")
+                .value_name("CHAR")
+                .help("CSV field delimiter, a single byte character (\\t denotes tab)")
+                .takes_value(true)
+                .required(false),
+        )
         .arg(
             Arg::with_name("timestamp")
                 .short("t")
@@ -71,11 +441,255 @@ fn main() {
                 .takes_value(true)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("ticks-as-string")
+                .long("ticks-as-string")
+                .help(
+                    "Render ticks timestamps as JSON strings, as they exceed the integers \
+                     exactly representable as doubles (e.g. in JavaScript)",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("pre-epoch")
+                .long("pre-epoch")
+                .possible_values(&["clamp", "null", "keep"])
+                .default_value("keep")
+                .help(
+                    "Timestamps before 1970-01-01: clamp (render as the epoch), \
+                     null or keep (render the actual timestamp)",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("timestamp-format")
+                .long("timestamp-format")
+                .value_name("FORMAT")
+                .help("strftime format string overriding the isostr timestamp rendering")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("time-as-number")
+                .long("time-as-number")
+                .help(
+                    "Keep top-level TIME (time of day) values as numbers of milli/microseconds \
+                     since midnight instead of HH:MM:SS.fff strings (nested TIME values are \
+                     always numbers)",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("timezone")
+                .long("timezone")
+                .value_name("TZ")
+                .help(
+                    "IANA timezone name (e.g. America/New_York) to render isostr timestamps in, \
+                     with the matching offset suffix instead of Z",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("bytes-as-string")
+                .long("bytes-as-string")
+                .help(
+                    "Render byte arrays which are valid UTF-8 as strings, \
+                     others with --bytes-encoding",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("bytes-encoding")
+                .long("bytes-encoding")
+                .possible_values(&["array", "base64", "hex"])
+                .default_value("array")
+                .help(
+                    "Byte array rendering option. Either \
+                     array (JSON array of byte values), \
+                     base64 (standard base64 string) \
+                     or hex (lowercase hex string)",
+                )
+                .takes_value(true)
+                .required(false),
+        )
         .arg(
             Arg::with_name("columns")
                 .short("c")
                 .long("columns")
-                .help("Comma separated top-level columns to select")
+                .help(
+                    "JSON array of top-level columns to select, in output order \
+                     (all columns in schema order by default), \
+                     an entry of the form src=dst renames column src to dst on output",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("columns-file")
+                .long("columns-file")
+                .value_name("PATH")
+                .help(
+                    "File with columns to select, one per line (blank lines and # comments \
+                     are ignored), combined with --columns",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("columns-regex")
+                .long("columns-regex")
+                .value_name("REGEX")
+                .help(
+                    "Select top-level columns whose names match the regular expression \
+                     (regex crate syntax, unanchored and case-sensitive unless written \
+                     with ^...$ or (?i)), in addition to --columns",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("add-filename-column")
+                .long("add-filename-column")
+                .value_name("NAME")
+                .help("Add column with the input file path of every row, as the last column")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("add-rownum-column")
+                .long("add-rownum-column")
+                .value_name("NAME")
+                .help(
+                    "Add column with the number of every row in its file, counting all rows \
+                     of the file (regardless of --row-groups, --where, --skip and the like), \
+                     before the file name column",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("rownum-base")
+                .long("rownum-base")
+                .possible_values(&["0", "1"])
+                .default_value("0")
+                .help("Number of the first row of a file with --add-rownum-column")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("strict-columns")
+                .long("strict-columns")
+                .help("Fail if any of the selected columns is missing in the file")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("limit")
+                .short("n")
+                .long("limit")
+                .visible_alias("head")
+                .value_name("N")
+                .help("Maximum number of rows to convert")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("skip")
+                .long("skip")
+                .value_name("N")
+                .help("Number of rows to skip before converting (applied before --limit)")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("resume-from")
+                .long("resume-from")
+                .value_name("ROWNUM")
+                .help(
+                    "Start converting at the given row index (of the selected row groups), \
+                     jumping over whole row groups with the row counts in the file metadata, \
+                     e.g. to resume an interrupted conversion with --append. \
+                     Applied before --where and --sample, --skip then skips rows past it",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("tail")
+                .long("tail")
+                .value_name("N")
+                .help(
+                    "Convert only the last N rows, located with the row counts in the file \
+                     metadata, so that preceding row groups are not read \
+                     (not supported with --where, --sample and --skip)",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("where")
+                .long("where")
+                .value_name("EXPR")
+                .help(
+                    "Only convert rows matching column==value (or !=, <, >, <=, >=) on a \
+                     top-level scalar column, compared as numbers when both sides are numeric. \
+                     The column is read even if not selected by --columns. \
+                     Rows where the column is missing or null don't match",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("sample")
+                .long("sample")
+                .value_name("RATE")
+                .help(
+                    "Emit a uniform random sample of approximately RATE (0..1) of the rows. \
+                     Independent of --limit, which can be combined to cap the sample size",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Random seed for --sample, for reproducible output")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("row-groups")
+                .long("row-groups")
+                .value_name("INDICES")
+                .help("Comma separated zero-based indices of row groups to convert")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("exclude-columns")
+                .long("exclude-columns")
+                .help(
+                    "Top-level columns to omit, either comma separated \
+                     or as a JSON array of column names",
+                )
+                .takes_value(true)
+                .conflicts_with_all(&["columns", "columns-file", "columns-regex"])
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("uuid-columns")
+                .long("uuid-columns")
+                .help(
+                    "Top-level 16-byte binary columns to render as GUID strings (typed guid \
+                     by --cslschema, --create-table and --mapping-out), \
+                     either comma separated or as a JSON array of column names",
+                )
                 .takes_value(true)
                 .required(false),
         )
@@ -95,6 +709,26 @@ fn main() {
                 .takes_value(false)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("schema-json")
+                .long("schema-json")
+                .help(
+                    "Print full schema tree as JSON, \
+                     including nested fields and list and map element types",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("json-schema")
+                .long("json-schema")
+                .help(
+                    "Print JSON Schema (draft-07) of the converted records, \
+                     following the rendering options (e.g. --timestamp)",
+                )
+                .takes_value(false)
+                .required(false),
+        )
         .arg(
             Arg::with_name("cslschema")
                 .long("cslschema")
@@ -102,19 +736,138 @@ fn main() {
                 .takes_value(false)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("type-override")
+                .long("type-override")
+                .value_name("COLUMN:TYPE,...")
+                .help(
+                    "Kusto types of top-level columns overriding the inferred ones \
+                     (e.g. payload:dynamic,id:long) with --cslschema, --create-table \
+                     and --mapping-out",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("transform")
+                .long("transform")
+                .value_name("COLUMN=FUNCTION(COLUMN)")
+                .help(
+                    "Transform values of a top-level column, with one of the functions \
+                     upper, lower, trim (of strings) or round(column[, digits]) (of floating \
+                     point numbers), e.g. name=upper(name). May be repeated for other columns",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("create-table")
+                .long("create-table")
+                .value_name("TABLE")
+                .help("Print Kusto .create table command for the schema")
+                .takes_value(true)
+                .required(false),
+        )
         .arg(
             Arg::with_name("rowgroups")
                 .long("rowgroups")
+                .visible_alias("row-groups-metadata")
                 .help("Print Row Groups Metadata")
                 .takes_value(false)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .help(
+                    "Print column statistics (min, max, null and distinct counts) \
+                     per row group as JSON, without reading the data",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("null-report")
+                .long("null-report")
+                .help(
+                    "Print null and total value counts per column as JSON, \
+                     scanning only column chunks without statistics",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .value_name("N")
+                .help(
+                    "Convert row groups in parallel with N threads (JSON output only), \
+                     output order is the same as with a single thread",
+                )
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("mmap")
+                .long("mmap")
+                .help("Memory-map input files instead of reading them (requires the mmap feature)")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("explain")
+                .long("explain")
+                .help(
+                    "Describe the planned conversion on STDERR (inputs, schema, columns, \
+                     output, settings and estimated row count) without converting",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("validate")
+                .long("validate")
+                .help(
+                    "Convert without writing the output, reporting the row count \
+                     or the first error with the file and the index of the row in it",
+                )
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("progress")
+                .long("progress")
+                .help("Report conversion progress to STDERR")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("count")
+                .long("count")
+                .help("Print the number of rows (read from metadata only)")
+                .takes_value(false)
+                .required(false),
+        )
         .arg(
             Arg::with_name("INPUT")
-                .help("Input file to use")
+                .help(
+                    "Input files to use, converted into a single output in the given order \
+                     (glob patterns are expanded, - to read from STDIN, \
+                     HTTP(S) URLs with the http feature)",
+                )
                 .required(true)
+                .multiple(true)
                 .index(1),
         )
+        .arg(
+            Arg::with_name("ignore-schema-mismatch")
+                .long("ignore-schema-mismatch")
+                .help("Convert multiple input files even if their schemas don't match")
+                .takes_value(false)
+                .required(false),
+        )
         .arg(
             Arg::with_name("v")
                 .short("v")
@@ -123,8 +876,21 @@ fn main() {
         )
         .get_matches();
 
-    let input = matches.value_of("INPUT").expect("INPUT must be provided");
-    let output = matches.value_of("OUT_FILE");
+    // Log messages go to STDERR, so they never mix with the converted output.
+    let log_level = match matches.occurrences_of("v") {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new().filter_level(log_level).init();
+
+    let inputs = expand_inputs(matches.values_of("INPUT").expect("INPUT must be provided"));
+    let inputs = inputs
+        .iter()
+        .map(|input| input.as_str())
+        .collect::<Vec<_>>();
+    let output = matches.value_of("output");
 
     let timestamp_rendering = match matches.value_of("timestamp").unwrap_or("ticks") {
         "ticks" => TimestampRendering::Ticks,
@@ -133,34 +899,506 @@ fn main() {
         _ => TimestampRendering::IsoStr,
     };
 
+    let on_duplicate_key = match matches.value_of("on-duplicate-key").unwrap_or("last") {
+        "first" => DuplicateKey::First,
+        "error" => DuplicateKey::Error,
+        "array" => DuplicateKey::Array,
+        _ => DuplicateKey::Last,
+    };
+
+    let pre_epoch = match matches.value_of("pre-epoch").unwrap_or("keep") {
+        "clamp" => PreEpoch::Clamp,
+        "null" => PreEpoch::Null,
+        _ => PreEpoch::Keep,
+    };
+
+    let bytes_encoding = match matches.value_of("bytes-encoding").unwrap_or("array") {
+        "base64" => BytesEncoding::Base64,
+        "hex" => BytesEncoding::Hex,
+        _ => BytesEncoding::Array,
+    };
+
+    let on_overflow = match matches.value_of("on-overflow").unwrap_or("keep") {
+        "string" => OverflowHandling::String,
+        "error" => OverflowHandling::Error,
+        _ => OverflowHandling::Keep,
+    };
+
+    let float_special = match matches.value_of("float-special").unwrap_or("null") {
+        "string" => FloatSpecial::String,
+        _ => FloatSpecial::Null,
+    };
+
+    let explode_empty = match matches.value_of("explode-empty").unwrap_or("drop") {
+        "null" => ExplodeEmpty::Null,
+        _ => ExplodeEmpty::Drop,
+    };
+
+    // Deprecated --csv and --json-array flags are honored unless --format is given explicitly.
+    let format = match matches.value_of("format").unwrap_or("jsonl") {
+        _ if matches.occurrences_of("format") == 0 && matches.is_present("csv") => {
+            OutputFormat::Csv
+        }
+        _ if matches.occurrences_of("format") == 0 && matches.is_present("json-array") => {
+            OutputFormat::JsonArray
+        }
+        "csv" => OutputFormat::Csv,
+        "tsv" => OutputFormat::Tsv,
+        "json-array" => OutputFormat::JsonArray,
+        "arrow" => OutputFormat::Arrow,
+        "kusto-multijson" => OutputFormat::KustoMultiJson,
+        _ => OutputFormat::Jsonl,
+    };
+
+    let newline = match matches.value_of("newline").unwrap_or("lf") {
+        "crlf" => Newline::Crlf,
+        _ => Newline::Lf,
+    };
+
+    let csv_quote = match matches.value_of("csv-quote").unwrap_or("necessary") {
+        "always" => CsvQuote::Always,
+        "non-numeric" => CsvQuote::NonNumeric,
+        "never" => CsvQuote::Never,
+        _ => CsvQuote::Necessary,
+    };
+
+    let output_encoding = match matches.value_of("output-encoding").unwrap_or("utf-8") {
+        "utf-16le" => OutputEncoding::Utf16Le,
+        _ => OutputEncoding::Utf8,
+    };
+
+    let csv_terminator = match matches.value_of("csv-terminator").unwrap_or("lf") {
+        "crlf" => CsvTerminator::Crlf,
+        "cr" => CsvTerminator::Cr,
+        _ => CsvTerminator::Lf,
+    };
+
+    let mut columns = matches.value_of("columns").map(|columns| {
+        serde_json::from_str::<Vec<String>>(columns).unwrap_or_else(|_| {
+            invalid_argument("Expected a well-formed JSON array of column names, each surrounded by escaped double-quotes")
+        })
+    });
+    if let Some(columns_file) = matches.value_of("columns-file") {
+        let columns = columns.get_or_insert_with(Vec::new);
+        columns.extend(read_columns_file(columns_file));
+        // Keep the first occurrence of every column.
+        let mut seen = HashSet::new();
+        columns.retain(|column| seen.insert(column.clone()));
+    }
+    let (columns, column_aliases) = match columns {
+        Some(columns) => {
+            let (columns, column_aliases) = parse_column_aliases(columns);
+            (Some(columns), column_aliases)
+        }
+        None => (None, HashMap::new()),
+    };
+
     let settings = Settings {
         omit_nulls: matches.is_present("omit-nulls") || matches.is_present("prune"),
+        keep_nulls: matches
+            .value_of("keep-nulls")
+            .map(parse_column_list)
+            .unwrap_or_default(),
         omit_empty_bags: matches.is_present("omit-empty-bags") || matches.is_present("prune"),
+        skip_empty_rows: matches.is_present("skip-empty-rows"),
         timestamp_rendering,
+        ticks_as_string: matches.is_present("ticks-as-string"),
+        pre_epoch,
+        timestamp_format: matches.value_of("timestamp-format").map(|format| {
+            validate_timestamp_format(format);
+            format.to_owned()
+        }),
+        time_as_number: matches.is_present("time-as-number"),
+        timezone: matches.value_of("timezone").map(|timezone| {
+            timezone.parse::<Tz>().unwrap_or_else(|e| {
+                invalid_argument(format!("Invalid timezone '{}': {}", timezone, e))
+            })
+        }),
         omit_empty_lists: matches.is_present("omit-empty-lists") || matches.is_present("prune"),
+        empty_string_as_null: matches.is_present("empty-string-as-null"),
         convert_types: matches.is_present("convert-types"),
-        columns: matches.value_of("columns").map(|columns| {
-            serde_json::from_str::<Vec<String>>(columns)
-                .expect("Expected a well-formed JSON array of column names, each surrounded by escaped double-quotes")
+        on_overflow,
+        float_special,
+        decimal_as_number: matches.is_present("decimal-as-number"),
+        long_as_string: matches.is_present("long-as-string"),
+        lenient: matches.is_present("lenient"),
+        on_duplicate_key,
+        max_depth: matches
+            .value_of("max-depth")
+            .unwrap_or("100")
+            .parse::<usize>()
+            .unwrap_or_else(|_| {
+                invalid_argument("Expected max depth to be a non-negative integer")
+            }),
+        columns,
+        column_aliases,
+        type_overrides: matches
+            .value_of("type-override")
+            .map(parse_type_overrides)
+            .unwrap_or_default(),
+        transforms: matches
+            .values_of("transform")
+            .map(parse_transforms)
+            .unwrap_or_default(),
+        columns_regex: matches.value_of("columns-regex").map(|regex| {
+            Regex::new(regex).unwrap_or_else(|e| {
+                invalid_argument(format!("Invalid --columns-regex '{}': {}", regex, e))
+            })
+        }),
+        exclude_columns: matches.value_of("exclude-columns").map(parse_column_list),
+        uuid_columns: matches
+            .value_of("uuid-columns")
+            .map(parse_column_list)
+            .unwrap_or_default(),
+        filename_column: matches
+            .value_of("add-filename-column")
+            .map(|name| name.to_owned()),
+        rownum_column: matches
+            .value_of("add-rownum-column")
+            .map(|name| name.to_owned()),
+        rownum_base: match matches.value_of("rownum-base").unwrap_or("0") {
+            "1" => 1,
+            _ => 0,
+        },
+        strict_columns: matches.is_present("strict-columns"),
+        ignore_schema_mismatch: matches.is_present("ignore-schema-mismatch"),
+        flatten: matches.is_present("flatten"),
+        flatten_separator: matches
+            .value_of("flatten-separator")
+            .unwrap_or(".")
+            .to_owned(),
+        explode: matches.value_of("explode").map(|column| column.to_owned()),
+        explode_empty,
+        format,
+        mapping_out: matches.value_of("mapping-out").map(|path| path.to_owned()),
+        csv_header: matches.is_present("csv-header"),
+        csv_null: matches.value_of("csv-null").unwrap_or("").to_owned(),
+        bool_as_int: matches.is_present("bool-as-int"),
+        newline,
+        csv_terminator,
+        csv_quote,
+        output_encoding,
+        csv_delimiter: matches
+            .value_of("delimiter")
+            .map(|delimiter| match delimiter {
+                "\\t" => b'\t',
+                _ if delimiter.len() == 1 => delimiter.as_bytes()[0],
+                _ => invalid_argument(format!(
+                    "Expected delimiter to be a single byte character, got '{}'",
+                    delimiter
+                )),
+            })
+            .unwrap_or(b','),
+        filter: matches
+            .value_of("where")
+            .map(|expr| RowFilter::parse(expr).unwrap_or_else(|e| invalid_argument(e))),
+        sample: matches.value_of("sample").map(|rate| {
+            rate.parse::<f64>()
+                .ok()
+                .filter(|rate| *rate >= 0.0 && *rate <= 1.0)
+                .unwrap_or_else(|| {
+                    invalid_argument("Expected sample rate to be a number between 0 and 1")
+                })
+        }),
+        seed: matches.value_of("seed").map(|seed| {
+            seed.parse::<u64>()
+                .unwrap_or_else(|_| invalid_argument("Expected seed to be a non-negative integer"))
+        }),
+        threads: matches
+            .value_of("threads")
+            .map(|threads| {
+                threads
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|threads| *threads > 0)
+                    .unwrap_or_else(|| {
+                        invalid_argument("Expected threads to be a positive integer")
+                    })
+            })
+            .unwrap_or(1),
+        mmap: matches.is_present("mmap"),
+        progress: matches.is_present("progress"),
+        pretty: matches.is_present("pretty"),
+        bytes_encoding,
+        bytes_as_string: matches.is_present("bytes-as-string"),
+        gzip: matches.is_present("gzip") || matches.is_present("gzip-level"),
+        gzip_level: matches
+            .value_of("gzip-level")
+            .map(|level| {
+                level
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|level| *level <= 9)
+                    .unwrap_or_else(|| {
+                        invalid_argument("Expected gzip level to be an integer between 0 and 9")
+                    })
+            })
+            .unwrap_or(6),
+        append: matches.is_present("append"),
+        per_row_group: matches.is_present("per-row-group"),
+        checksum: matches.is_present("checksum") || matches.is_present("checksum-out"),
+        checksum_out: matches.value_of("checksum-out").map(|path| path.to_owned()),
+        limit: matches.value_of("limit").map(|limit| {
+            limit
+                .parse::<usize>()
+                .unwrap_or_else(|_| invalid_argument("Expected limit to be a non-negative integer"))
+        }),
+        skip: matches
+            .value_of("skip")
+            .map(|skip| {
+                skip.parse::<usize>().unwrap_or_else(|_| {
+                    invalid_argument("Expected skip to be a non-negative integer")
+                })
+            })
+            .unwrap_or(0),
+        resume_from: matches
+            .value_of("resume-from")
+            .map(|resume_from| {
+                resume_from.parse::<usize>().unwrap_or_else(|_| {
+                    invalid_argument("Expected resume row index to be a non-negative integer")
+                })
+            })
+            .unwrap_or(0),
+        tail: matches.value_of("tail").map(|tail| {
+            tail.parse::<usize>()
+                .unwrap_or_else(|_| invalid_argument("Expected tail to be a non-negative integer"))
+        }),
+        buffer_size: matches.value_of("buffer-size").map(|buffer_size| {
+            parse_byte_size(buffer_size)
+                .filter(|buffer_size| *buffer_size > 0)
+                .map(|buffer_size| buffer_size as usize)
+                .unwrap_or_else(|| {
+                    invalid_argument("Expected buffer size to be a positive size, e.g. 64K, 1M")
+                })
+        }),
+        split_rows: matches.value_of("split-rows").map(|split_rows| {
+            split_rows
+                .parse::<usize>()
+                .ok()
+                .filter(|split_rows| *split_rows > 0)
+                .unwrap_or_else(|| invalid_argument("Expected split rows to be a positive integer"))
+        }),
+        split_bytes: matches.value_of("split-bytes").map(|split_bytes| {
+            parse_byte_size(split_bytes)
+                .filter(|split_bytes| *split_bytes > 0)
+                .unwrap_or_else(|| {
+                    invalid_argument(
+                        "Expected split bytes to be a positive size, e.g. 500K, 100M, 1G",
+                    )
+                })
+        }),
+        row_groups: matches.value_of("row-groups").map(|row_groups| {
+            row_groups
+                .split(',')
+                .map(|i| {
+                    i.trim().parse::<usize>().unwrap_or_else(|_| {
+                        invalid_argument("Expected a comma separated list of row group indices")
+                    })
+                })
+                .collect()
         }),
-        csv: matches.is_present("csv"),
     };
 
+    // Metadata is printed for every input file.
     let res = if matches.is_present("schema") {
-        schema::print_schema(input)
+        inputs
+            .iter()
+            .try_for_each(|input| pq2json::print_schema(input))
+    } else if matches.is_present("schema-json") {
+        inputs
+            .iter()
+            .try_for_each(|input| pq2json::print_json_schema(input))
+    } else if matches.is_present("json-schema") {
+        inputs
+            .iter()
+            .try_for_each(|input| pq2json::print_record_json_schema(&settings, input))
     } else if matches.is_present("cslschema") {
-        schema::print_csl_schema(input)
+        inputs
+            .iter()
+            .try_for_each(|input| pq2json::print_csl_schema(&settings, input))
+    } else if let Some(table_name) = matches.value_of("create-table") {
+        inputs
+            .iter()
+            .try_for_each(|input| pq2json::print_create_table(&settings, input, table_name))
     } else if matches.is_present("rowgroups") {
-        schema::print_row_groups_metadata(input)
+        inputs
+            .iter()
+            .try_for_each(|input| pq2json::print_row_groups_metadata(input))
+    } else if matches.is_present("stats") {
+        inputs
+            .iter()
+            .try_for_each(|input| pq2json::print_statistics(input))
+    } else if matches.is_present("null-report") {
+        inputs
+            .iter()
+            .try_for_each(|input| pq2json::print_null_report(input))
+    } else if matches.is_present("count") {
+        inputs
+            .iter()
+            .try_for_each(|input| pq2json::print_row_count(input))
+    } else if matches.is_present("explain") {
+        pq2json::explain_files(&settings, &inputs, output)
+    } else if matches.is_present("validate") {
+        pq2json::validate_files(&settings, &inputs)
     } else {
-        converter::convert(&settings, input, output)
+        pq2json::convert_files(&settings, &inputs, output)
     };
 
     match res {
         Ok(()) => (),
         Err(e) => {
-            eprintln!("ERROR: {:?}", e);
-            std::process::exit(-1);
+            eprintln!("ERROR: {}", e);
+            process::exit(-1);
         }
     }
 }
+
+/// Expands glob patterns among input arguments into matching paths (sorted),
+/// other arguments are used as is.
+fn expand_inputs<'a>(inputs: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        // URLs are never expanded, '?' starts their query string.
+        let is_url = input.starts_with("http://") || input.starts_with("https://");
+        if is_url || !input.contains(|c| c == '*' || c == '?' || c == '[') {
+            paths.push(input.to_owned());
+            continue;
+        }
+        let matches = glob::glob(input)
+            .unwrap_or_else(|e| {
+                invalid_argument(format!("Invalid glob pattern '{}': {}", input, e))
+            })
+            .map(|path| match path {
+                Ok(path) => path.to_string_lossy().into_owned(),
+                Err(e) => invalid_argument(format!("Failed to read {}: {}", input, e)),
+            })
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            invalid_argument(format!("No files match '{}'", input));
+        }
+        paths.extend(matches);
+    }
+    paths
+}
+
+/// Parses size in bytes with an optional binary K, M or G suffix.
+fn parse_byte_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_ascii_uppercase();
+    let size = size.trim_end_matches('B');
+    let (digits, multiplier) = match size.chars().last()? {
+        'K' => (&size[..size.len() - 1], 1u64 << 10),
+        'M' => (&size[..size.len() - 1], 1u64 << 20),
+        'G' => (&size[..size.len() - 1], 1u64 << 30),
+        _ => (size, 1),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Splits `src=dst` column entries into source column names and a rename map.
+fn parse_column_aliases(columns: Vec<String>) -> (Vec<String>, HashMap<String, String>) {
+    let mut column_aliases = HashMap::new();
+    let columns = columns
+        .into_iter()
+        .map(|column| {
+            let mut parts = column.splitn(2, '=');
+            let name = parts.next().unwrap_or_default().to_owned();
+            if let Some(alias) = parts.next() {
+                column_aliases.insert(name.clone(), alias.to_owned());
+            }
+            name
+        })
+        .collect();
+    (columns, column_aliases)
+}
+
+/// Reads column names from a file, one per line, skipping blank lines and `#` comments.
+fn read_columns_file(path: &str) -> Vec<String> {
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        invalid_argument(format!("Failed to read columns file {}: {}", path, e))
+    });
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_owned())
+        .collect()
+}
+
+/// Parses a list of column names, given either as a JSON array or comma separated.
+fn parse_column_list(columns: &str) -> Vec<String> {
+    if columns.trim_start().starts_with('[') {
+        serde_json::from_str::<Vec<String>>(columns).unwrap_or_else(|_| {
+            invalid_argument("Expected a well-formed JSON array of column names, each surrounded by escaped double-quotes")
+        })
+    } else {
+        columns
+            .split(',')
+            .map(|column| column.trim().to_owned())
+            .filter(|column| !column.is_empty())
+            .collect()
+    }
+}
+
+/// Parses `column:type` pairs, comma separated, type being a Kusto scalar type.
+fn parse_type_overrides(overrides: &str) -> HashMap<String, String> {
+    overrides
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            let (column, csl_type) = match pair.rfind(':') {
+                Some(pos) => (pair[..pos].trim(), pair[pos + 1..].trim()),
+                None => invalid_argument(format!(
+                    "Expected type override of the form column:type, got '{}'",
+                    pair
+                )),
+            };
+            if !CSL_TYPES.contains(&csl_type) {
+                invalid_argument(format!(
+                    "Unknown Kusto type '{}' of column {}, expected one of: {}",
+                    csl_type,
+                    column,
+                    CSL_TYPES.join(", ")
+                ));
+            }
+            (column.to_owned(), csl_type.to_owned())
+        })
+        .collect()
+}
+
+/// Parses --transform entries, at most one per column.
+fn parse_transforms<'a>(transforms: impl Iterator<Item = &'a str>) -> HashMap<String, Transform> {
+    let mut parsed = HashMap::new();
+    for transform in transforms {
+        let (column, transform) =
+            Transform::parse(transform).unwrap_or_else(|e| invalid_argument(e));
+        if parsed.contains_key(&column) {
+            invalid_argument(format!(
+                "--transform is given more than once for column {}",
+                column
+            ));
+        }
+        parsed.insert(column, transform);
+    }
+    parsed
+}
+
+const CSL_TYPES: &[&str] = &[
+    "bool", "datetime", "decimal", "dynamic", "guid", "int", "long", "real", "string", "timespan",
+];
+
+/// Fails fast on a malformed strftime format by rendering a sample datetime.
+fn validate_timestamp_format(format: &str) {
+    use std::fmt::Write;
+
+    let sample = chrono::NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 0, 0);
+    let mut output = String::new();
+    if write!(output, "{}", sample.format(format)).is_err() {
+        invalid_argument(format!("Invalid timestamp format '{}'", format));
+    }
+}
+
+/// Reports an invalid command line argument and exits, with the exit code of clap usage errors.
+fn invalid_argument(message: impl fmt::Display) -> ! {
+    eprintln!("ERROR: {}", message);
+    process::exit(1);
+}
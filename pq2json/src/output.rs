@@ -0,0 +1,764 @@
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::info;
+use sha2::{Digest, Sha256};
+
+use crate::settings::{OutputEncoding, OutputFormat, Settings};
+
+/// Default output buffer capacity, unless --buffer-size is specified.
+const WRITER_BUF_CAP: usize = 256 * 1024;
+
+/// Destination of the converted data: a single stream, or with --split-rows / --split-bytes
+/// a sequence of part files named after the output path
+/// (`out.jsonl` -> `out.0.jsonl`, `out.1.jsonl`, ...).
+///
+/// Output files are written as `<file>.tmp` and renamed into place by `finish`, so that
+/// a failed conversion doesn't leave a partial file behind (temporary files are deleted
+/// when the output is dropped unfinished). With --append, and for outputs which aren't
+/// regular files (named pipes, devices such as `/dev/stdout`), files are written in place.
+pub(crate) struct Output<'a> {
+    settings: &'a Settings,
+    target: Target,
+    parts: usize,
+    /// Bytes written to the current part (before compression).
+    part_bytes: Rc<Cell<u64>>,
+    /// Whether the current part is appended to a non-empty file (with --append).
+    appending: bool,
+    /// Temporary files written, each with its final path.
+    temp_files: Vec<(String, String)>,
+    /// First failure of closing a writer (see `OutputWriter`).
+    close_error: CloseError,
+    /// --checksum lines of the closed writers, reported by `finish`.
+    checksums: Checksums,
+}
+
+type CloseError = Rc<RefCell<Option<io::Error>>>;
+
+type Checksums = Rc<RefCell<Vec<String>>>;
+
+enum Target {
+    Stream(Option<Box<dyn Write>>),
+    Files(String),
+}
+
+impl<'a> Output<'a> {
+    /// Output to the specified file, or STDOUT if not provided.
+    pub(crate) fn file(
+        settings: &'a Settings,
+        output_file: Option<&str>,
+    ) -> Result<Self, Box<dyn Error>> {
+        if settings.append
+            && (settings.format == OutputFormat::JsonArray
+                || settings.format == OutputFormat::Arrow)
+        {
+            return Err("--append is not supported with json-array and arrow formats".into());
+        }
+        if settings.append && settings.checksum {
+            return Err("--checksum is not supported with --append".into());
+        }
+        check_output_encoding(settings)?;
+        let mut appending = false;
+        let mut temp_files = Vec::new();
+        let close_error = CloseError::default();
+        let checksums = Checksums::default();
+        let target = match (output_file, is_split(settings)) {
+            (None, true) => return Err("Splitting output requires an output file".into()),
+            (Some(output_file), true) => Target::Files(output_file.to_owned()),
+            (output_file, false) => {
+                let (sink, non_empty) = create_sink(settings, output_file, &mut temp_files)?;
+                appending = non_empty;
+                let sink = checksum_sink(settings, sink, output_file.unwrap_or("-"), &checksums);
+                Target::Stream(Some(create_writer(settings, sink, &close_error)))
+            }
+        };
+        Ok(Output {
+            settings,
+            target,
+            parts: 0,
+            part_bytes: Rc::new(Cell::new(0)),
+            appending,
+            temp_files,
+            close_error,
+            checksums,
+        })
+    }
+
+    /// Output to a caller-provided stream, which can't be split.
+    pub(crate) fn stream(
+        settings: &'a Settings,
+        stream: Box<dyn Write>,
+    ) -> Result<Self, Box<dyn Error>> {
+        if is_split(settings) {
+            return Err("Splitting output requires an output file".into());
+        }
+        check_output_encoding(settings)?;
+        truncate_checksum_file(settings)?;
+        let checksums = Checksums::default();
+        let stream = checksum_sink(settings, stream, "-", &checksums);
+        let close_error = CloseError::default();
+        Ok(Output {
+            settings,
+            target: Target::Stream(Some(create_writer(settings, stream, &close_error))),
+            parts: 0,
+            part_bytes: Rc::new(Cell::new(0)),
+            appending: false,
+            temp_files: Vec::new(),
+            close_error,
+            checksums,
+        })
+    }
+
+    /// Returns writer for the next output part, single stream output has just one part.
+    pub(crate) fn next_writer(&mut self) -> Result<Box<dyn Write>, Box<dyn Error>> {
+        let writer = match self.target {
+            Target::Stream(ref mut writer) => writer
+                .take()
+                .ok_or("Output stream has been already written")?,
+            Target::Files(ref output_file) => {
+                let part_file = part_path(output_file, self.parts);
+                info!("Writing {}", part_file);
+                let (sink, non_empty) =
+                    create_sink(self.settings, Some(&part_file), &mut self.temp_files)?;
+                self.appending = non_empty;
+                let sink = checksum_sink(self.settings, sink, &part_file, &self.checksums);
+                create_writer(self.settings, sink, &self.close_error)
+            }
+        };
+        self.parts += 1;
+        self.part_bytes.set(0);
+        Ok(Box::new(CountingWriter {
+            inner: writer,
+            count: self.part_bytes.clone(),
+        }))
+    }
+
+    /// Renames the temporary output files into place, once all writers are dropped
+    /// (i.e. flushed), unless closing any of them failed. Checksums are reported only
+    /// once all output files are in place.
+    pub(crate) fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.close_stream();
+        let close_error = self.close_error.borrow_mut().take();
+        if let Some(e) = close_error {
+            // Temporary files are deleted on drop.
+            return Err(format!("Failed to write output: {}", e).into());
+        }
+        while !self.temp_files.is_empty() {
+            let (temp_file, output_file) = self.temp_files.remove(0);
+            if let Err(e) = fs::rename(&temp_file, &output_file) {
+                // Remaining temporary files are deleted on drop.
+                let _ = fs::remove_file(&temp_file);
+                return Err(
+                    format!("Failed to rename {} to {}: {}", temp_file, output_file, e).into(),
+                );
+            }
+        }
+        let checksums = self.checksums.borrow_mut().split_off(0);
+        report_checksums(self.settings, &checksums)
+    }
+
+    /// Drops the stream writer, unless it has been already taken.
+    fn close_stream(&mut self) {
+        if let Target::Stream(ref mut writer) = self.target {
+            writer.take();
+        }
+    }
+
+    /// Whether the current part is appended to existing data, e.g. so that
+    /// the CSV header is not repeated.
+    pub(crate) fn is_appending(&self) -> bool {
+        self.appending
+    }
+
+    /// Whether the current part, having `records` written, is complete,
+    /// so that the next record goes to a new part.
+    ///
+    /// Byte count doesn't include data buffered by the record writer (e.g. CSV writer),
+    /// so parts may exceed --split-bytes by up to one buffer.
+    pub(crate) fn is_part_full(&self, records: usize) -> bool {
+        let rows_full = self
+            .settings
+            .split_rows
+            .map_or(false, |split_rows| records >= split_rows);
+        let bytes_full = self
+            .settings
+            .split_bytes
+            .map_or(false, |split_bytes| self.part_bytes.get() >= split_bytes);
+        rows_full || bytes_full
+    }
+}
+
+impl Drop for Output<'_> {
+    fn drop(&mut self) {
+        // Temporary file can't be deleted while open on some platforms.
+        self.close_stream();
+        for (temp_file, _) in self.temp_files.drain(..) {
+            info!("Deleting {}", temp_file);
+            let _ = fs::remove_file(&temp_file);
+        }
+    }
+}
+
+fn is_split(settings: &Settings) -> bool {
+    settings.split_rows.is_some() || settings.split_bytes.is_some()
+}
+
+fn check_output_encoding(settings: &Settings) -> Result<(), Box<dyn Error>> {
+    if settings.output_encoding == OutputEncoding::Utf8 {
+        return Ok(());
+    }
+    if settings.format != OutputFormat::Csv && settings.format != OutputFormat::Tsv {
+        return Err("--output-encoding utf-16le is only supported with csv and tsv formats".into());
+    }
+    if settings.append {
+        // The byte order mark would end up in the middle of the file.
+        return Err("--output-encoding utf-16le is not supported with --append".into());
+    }
+    Ok(())
+}
+
+/// Writer counting bytes written through it.
+struct CountingWriter {
+    inner: Box<dyn Write>,
+    count: Rc<Cell<u64>>,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count.set(self.count.get() + written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writer transcoding UTF-8 written through it to UTF-16LE, preceded by a byte order mark.
+/// A character split between writes is held back until its remaining bytes arrive.
+struct Utf16Writer {
+    inner: Box<dyn Write>,
+    pending: Vec<u8>,
+    /// Buffer of the UTF-16LE bytes of a write, reused across writes.
+    encoded: Vec<u8>,
+    bom_written: bool,
+}
+
+impl Write for Utf16Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Completes the held back character from the first bytes of the buffer. Nothing is
+        // held back or written before the whole buffer is validated.
+        let mut head = [0; 4];
+        let mut completed = "";
+        let mut consumed = 0;
+        if !self.pending.is_empty() {
+            let held = self.pending.len();
+            let taken = buf.len().min(head.len() - held);
+            head[..held].copy_from_slice(&self.pending);
+            head[held..held + taken].copy_from_slice(&buf[..taken]);
+            let text = valid_utf8_prefix(&head[..held + taken])?;
+            match text.chars().next() {
+                Some(c) => {
+                    completed = &text[..c.len_utf8()];
+                    consumed = c.len_utf8() - held;
+                }
+                None => {
+                    self.pending.extend_from_slice(buf);
+                    return Ok(buf.len());
+                }
+            }
+        }
+        let text = valid_utf8_prefix(&buf[consumed..])?;
+
+        self.encoded.clear();
+        if !self.bom_written {
+            self.encoded.extend_from_slice(&[0xFF, 0xFE]);
+        }
+        for unit in completed.encode_utf16().chain(text.encode_utf16()) {
+            self.encoded.extend_from_slice(&unit.to_le_bytes());
+        }
+        self.inner.write_all(&self.encoded)?;
+        self.bom_written = true;
+        self.pending.clear();
+        self.pending
+            .extend_from_slice(&buf[consumed + text.len()..]);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns the bytes as text, up to a character whose remaining bytes are still to come.
+fn valid_utf8_prefix(bytes: &[u8]) -> io::Result<&str> {
+    let invalid_data = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(text),
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..e.valid_up_to()]).map_err(invalid_data)
+        }
+        Err(e) => Err(invalid_data(e)),
+    }
+}
+
+/// Empties the --checksum-out file, once per conversion: checksums of the output files
+/// (parts or row groups) are appended to it one by one, as they are completed.
+pub(crate) fn truncate_checksum_file(settings: &Settings) -> Result<(), Box<dyn Error>> {
+    if let Some(ref checksum_file) = settings.checksum_out {
+        File::create(&Path::new(checksum_file))?;
+    }
+    Ok(())
+}
+
+/// Wraps the sink with --checksum hashing of the bytes written to it, i.e. after compression.
+fn checksum_sink(
+    settings: &Settings,
+    sink: Box<dyn Write>,
+    output_file: &str,
+    checksums: &Checksums,
+) -> Box<dyn Write> {
+    if settings.checksum {
+        Box::new(HashingWriter {
+            inner: sink,
+            hasher: Some(Sha256::new()),
+            output_file: output_file.to_owned(),
+            checksums: checksums.clone(),
+        })
+    } else {
+        sink
+    }
+}
+
+/// Writer computing SHA-256 of the bytes written through it. The checksum line
+/// (`sha256sum` format) is recorded once the writer is dropped, when all data
+/// (e.g. the gzip trailer) has been written, and reported by `Output::finish`.
+struct HashingWriter {
+    inner: Box<dyn Write>,
+    hasher: Option<Sha256>,
+    output_file: String,
+    checksums: Checksums,
+}
+
+impl Write for HashingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(ref mut hasher) = self.hasher {
+            hasher.update(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Drop for HashingWriter {
+    fn drop(&mut self) {
+        let digest = match self.hasher.take() {
+            Some(hasher) => hasher.finalize(),
+            None => return,
+        };
+        let line = format!(
+            "{}  {}",
+            digest
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>(),
+            self.output_file
+        );
+        self.checksums.borrow_mut().push(line);
+    }
+}
+
+/// Prints the checksum lines to STDERR, or appends them to the --checksum-out file.
+fn report_checksums(settings: &Settings, checksums: &[String]) -> Result<(), Box<dyn Error>> {
+    match settings.checksum_out {
+        Some(ref checksum_file) if !checksums.is_empty() => OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&Path::new(checksum_file))
+            .and_then(|mut file| {
+                checksums
+                    .iter()
+                    .try_for_each(|line| writeln!(file, "{}", line))
+            })
+            .map_err(|e| format!("Failed to write checksum to {}: {}", checksum_file, e))?,
+        Some(_) => (),
+        None => checksums.iter().for_each(|line| eprintln!("{}", line)),
+    }
+    Ok(())
+}
+
+/// Inserts part index before the file extension(s), e.g. `out.jsonl.gz` -> `out.1.jsonl.gz`.
+fn part_path(output_file: &str, index: usize) -> String {
+    path_with_infix(output_file, &index.to_string())
+}
+
+/// Output file of the `index`-th row group with --per-row-group,
+/// e.g. `out.jsonl.gz` -> `out.rg1.jsonl.gz`.
+pub(crate) fn row_group_path(output_file: &str, index: usize) -> String {
+    path_with_infix(output_file, &format!("rg{}", index))
+}
+
+fn path_with_infix(output_file: &str, infix: &str) -> String {
+    let path = Path::new(output_file);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let part_name = match file_name.find('.') {
+        Some(pos) if pos > 0 => format!("{}.{}{}", &file_name[..pos], infix, &file_name[pos..]),
+        _ => format!("{}.{}", file_name, infix),
+    };
+    path.with_file_name(part_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Opens the output file, with --append appending to it, otherwise creating its temporary
+/// file (added to `temp_files`), also returning whether the file already has data.
+/// Existing outputs other than regular files are opened for writing in place.
+fn create_sink(
+    settings: &Settings,
+    output_file: Option<&str>,
+    temp_files: &mut Vec<(String, String)>,
+) -> Result<(Box<dyn Write>, bool), Box<dyn Error>> {
+    match output_file {
+        Some(output_file) if settings.append => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&Path::new(output_file))?;
+            let non_empty = file.metadata()?.len() > 0;
+            Ok((Box::new(file), non_empty))
+        }
+        Some(output_file) if !is_replaceable(output_file) => {
+            let file = OpenOptions::new()
+                .write(true)
+                .open(&Path::new(output_file))?;
+            Ok((Box::new(file), false))
+        }
+        Some(output_file) => {
+            let temp_file = format!("{}.tmp", output_file);
+            let file = File::create(&Path::new(&temp_file))?;
+            temp_files.push((temp_file, output_file.to_owned()));
+            Ok((Box::new(file), false))
+        }
+        None => Ok((Box::new(io::stdout()), false)),
+    }
+}
+
+/// Whether the output file can be replaced by renaming its temporary file over it: it doesn't
+/// exist yet or is a regular file. Symbolic links (e.g. `/dev/stdout`) are not followed,
+/// renaming would replace the link rather than write to its target.
+fn is_replaceable(output_file: &str) -> bool {
+    match fs::symlink_metadata(output_file) {
+        Ok(metadata) => metadata.file_type().is_file(),
+        Err(_) => true,
+    }
+}
+
+fn create_writer(
+    settings: &Settings,
+    sink: Box<dyn Write>,
+    close_error: &CloseError,
+) -> Box<dyn Write> {
+    let sink = if settings.gzip {
+        Sink::Gzip(GzEncoder::new(sink, Compression::new(settings.gzip_level)))
+    } else {
+        Sink::Plain(sink)
+    };
+    let writer: Box<dyn Write> = Box::new(OutputWriter {
+        inner: Some(BufWriter::with_capacity(
+            settings.buffer_size.unwrap_or(WRITER_BUF_CAP),
+            sink,
+        )),
+        close_error: close_error.clone(),
+    });
+    match settings.output_encoding {
+        OutputEncoding::Utf8 => writer,
+        OutputEncoding::Utf16Le => Box::new(Utf16Writer {
+            inner: writer,
+            pending: Vec::new(),
+            encoded: Vec::new(),
+            bom_written: false,
+        }),
+    }
+}
+
+enum Sink {
+    Plain(Box<dyn Write>),
+    Gzip(GzEncoder<Box<dyn Write>>),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(sink) => sink.write(buf),
+            Sink::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(sink) => sink.flush(),
+            Sink::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Buffered, optionally gzipped, output writer, closed when dropped. Unlike dropping
+/// `BufWriter` and `GzEncoder`, which ignores failures, failures of the final flush
+/// and of writing the gzip trailer are recorded, so that `Output::finish` doesn't rename
+/// an incomplete file into place.
+struct OutputWriter {
+    inner: Option<BufWriter<Sink>>,
+    close_error: CloseError,
+}
+
+impl OutputWriter {
+    fn writer(&mut self) -> io::Result<&mut BufWriter<Sink>> {
+        self.inner
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Output writer is closed"))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        let mut writer = match self.inner.take() {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+        writer.flush()?;
+        let sink = writer
+            .into_inner()
+            .map_err(|e| io::Error::new(e.error().kind(), e.error().to_string()))?;
+        match sink {
+            Sink::Plain(mut sink) => sink.flush(),
+            Sink::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer()?.flush()
+    }
+}
+
+impl Drop for OutputWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            let mut close_error = self.close_error.borrow_mut();
+            if close_error.is_none() {
+                *close_error = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// Sink accepting writes but failing to flush, e.g. like a full disk.
+    struct FailingFlush;
+
+    impl Write for FailingFlush {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+        }
+    }
+
+    fn output_writer(sink: Sink, close_error: &CloseError) -> OutputWriter {
+        OutputWriter {
+            inner: Some(BufWriter::new(sink)),
+            close_error: close_error.clone(),
+        }
+    }
+
+    #[test]
+    fn close_failure_is_recorded() {
+        let close_error = CloseError::default();
+        let mut writer = output_writer(Sink::Plain(Box::new(FailingFlush)), &close_error);
+        writer.write_all(b"data").unwrap();
+        drop(writer);
+        assert_eq!(
+            close_error.borrow().as_ref().map(|e| e.to_string()),
+            Some("disk full".to_owned())
+        );
+    }
+
+    #[test]
+    fn gzip_trailer_failure_is_recorded() {
+        let close_error = CloseError::default();
+        let encoder = GzEncoder::new(
+            Box::new(FailingFlush) as Box<dyn Write>,
+            Compression::new(6),
+        );
+        let mut writer = output_writer(Sink::Gzip(encoder), &close_error);
+        writer.write_all(b"data").unwrap();
+        drop(writer);
+        assert!(close_error.borrow().is_some());
+    }
+
+    #[test]
+    fn successful_close_records_nothing() {
+        let close_error = CloseError::default();
+        let mut writer = output_writer(Sink::Plain(Box::new(io::sink())), &close_error);
+        writer.write_all(b"data").unwrap();
+        drop(writer);
+        assert!(close_error.borrow().is_none());
+    }
+
+    #[test]
+    fn regular_output_file_is_written_through_temporary_file() {
+        let output_file = env::temp_dir().join(format!("pq2json-{}-sink.jsonl", process::id()));
+        let output_file = output_file.to_str().unwrap();
+        let mut temp_files = Vec::new();
+        let (mut sink, _) =
+            create_sink(&Settings::default(), Some(output_file), &mut temp_files).unwrap();
+        sink.write_all(b"data").unwrap();
+        drop(sink);
+        assert_eq!(
+            temp_files,
+            vec![(format!("{}.tmp", output_file), output_file.to_owned())]
+        );
+        assert!(!Path::new(output_file).exists());
+        fs::remove_file(&temp_files[0].0).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn device_output_is_written_in_place() {
+        let mut temp_files = Vec::new();
+        let (mut sink, non_empty) =
+            create_sink(&Settings::default(), Some("/dev/null"), &mut temp_files).unwrap();
+        sink.write_all(b"data").unwrap();
+        assert!(temp_files.is_empty());
+        assert!(!non_empty);
+    }
+
+    fn checksum_settings(name: &str) -> Settings {
+        let checksum_file = env::temp_dir().join(format!("pq2json-{}-{}", process::id(), name));
+        Settings {
+            checksum: true,
+            checksum_out: Some(checksum_file.to_string_lossy().into_owned()),
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn checksum_is_reported_by_finish() {
+        let settings = checksum_settings("checksum-finished.sha256");
+        let mut output = Output::stream(&settings, Box::new(io::sink())).unwrap();
+        output.next_writer().unwrap().write_all(b"data").unwrap();
+        let checksum_file = settings.checksum_out.as_ref().unwrap();
+        assert_eq!(fs::read_to_string(checksum_file).unwrap(), "");
+        output.finish().unwrap();
+        assert_eq!(
+            fs::read_to_string(checksum_file).unwrap(),
+            "3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7  -\n"
+        );
+        fs::remove_file(checksum_file).unwrap();
+    }
+
+    #[test]
+    fn checksum_is_not_reported_for_failed_output() {
+        let settings = checksum_settings("checksum-failed.sha256");
+        let mut output = Output::stream(&settings, Box::new(FailingFlush)).unwrap();
+        output.next_writer().unwrap().write_all(b"data").unwrap();
+        assert!(output.finish().is_err());
+        let checksum_file = settings.checksum_out.as_ref().unwrap();
+        assert_eq!(fs::read_to_string(checksum_file).unwrap(), "");
+        fs::remove_file(checksum_file).unwrap();
+    }
+
+    #[test]
+    fn part_paths_keep_extensions() {
+        assert_eq!(part_path("out.jsonl.gz", 1), "out.1.jsonl.gz");
+        assert_eq!(part_path("dir/out", 0), "dir/out.0");
+        assert_eq!(row_group_path("out.jsonl", 2), "out.rg2.jsonl");
+    }
+
+    /// Sink whose bytes remain readable after being moved into a writer.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn utf16_writer(sink: &SharedBuffer) -> Utf16Writer {
+        Utf16Writer {
+            inner: Box::new(sink.clone()),
+            pending: Vec::new(),
+            encoded: Vec::new(),
+            bom_written: false,
+        }
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn utf16_characters_split_between_writes_are_transcoded() {
+        let text = "a\u{20AC}\u{1F600}b";
+        for split in 0..=text.len() {
+            let sink = SharedBuffer::default();
+            let mut writer = utf16_writer(&sink);
+            writer.write_all(&text.as_bytes()[..split]).unwrap();
+            for byte in &text.as_bytes()[split..] {
+                writer.write_all(&[*byte]).unwrap();
+            }
+            assert_eq!(*sink.0.borrow(), utf16le(text), "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_is_rejected_without_being_held_back() {
+        let sink = SharedBuffer::default();
+        let mut writer = utf16_writer(&sink);
+        writer.write_all(b"a\xE2\x82").unwrap();
+        assert_eq!(
+            writer.write(b"\xFF").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            writer.write(b"b\xFF").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        writer.write_all(b"\xACb").unwrap();
+        assert_eq!(*sink.0.borrow(), utf16le("a\u{20AC}b"));
+    }
+}
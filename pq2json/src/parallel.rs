@@ -0,0 +1,428 @@
+use std::any::Any;
+use std::error::Error;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use log::{debug, warn};
+use parquet::file::reader::{FileReader, RowGroupReader};
+use parquet::schema::types::{Type as SchemaType, TypePtr};
+
+use crate::converter::{
+    add_synthetic_columns, explode_row, is_skipped_empty_row, row_matches, top_level_row_to_value,
+    value_to_json,
+};
+use crate::error::{ConvertError, RowError};
+use crate::input::{is_url, open_input, STDIN_INPUT};
+use crate::settings::{OutputFormat, Settings};
+
+/// Serialized records of a single row group, or the conversion error.
+type RowGroupRecords = Result<Vec<String>, WorkerError>;
+
+/// Conversion error sent back by a worker. Errors can't be sent between threads, so they are
+/// sent as their message, along with the location of row errors, from which the reading
+/// thread rebuilds `RowError` (same as single-threaded conversion).
+#[derive(Debug)]
+enum WorkerError {
+    Row {
+        file: Option<String>,
+        row: usize,
+        column: Option<String>,
+        message: String,
+    },
+    Other(String),
+}
+
+impl WorkerError {
+    fn new(error: Box<dyn Error>) -> Self {
+        match error.downcast::<RowError>() {
+            Ok(error) => WorkerError::Row {
+                file: error.file,
+                row: error.row,
+                column: error.column,
+                message: error.source.to_string(),
+            },
+            Err(error) => WorkerError::Other(error.to_string()),
+        }
+    }
+
+    fn into_error(self) -> Box<dyn Error> {
+        match self {
+            WorkerError::Row {
+                file,
+                row,
+                column,
+                message,
+            } => Box::new(RowError {
+                file,
+                row,
+                column,
+                source: Box::new(ConvertError::Other(message.into())),
+            }),
+            WorkerError::Other(message) => message.into(),
+        }
+    }
+}
+
+/// Returns serialized JSON records of the files, converting row groups in parallel
+/// with --threads worker threads.
+///
+/// Row groups are assigned to workers round-robin, and every worker sends the records
+/// of its row groups over its own bounded channel, so that they are read back
+/// in file and row group order (same as single-threaded output), while at most
+/// a couple of row groups per worker are buffered.
+pub(crate) fn parallel_records(
+    settings: &Settings,
+    input_files: &[&str],
+    readers: &[Box<dyn FileReader>],
+    schema: Option<SchemaType>,
+    fields: Vec<TypePtr>,
+) -> Result<ParallelRecords, Box<dyn Error>> {
+    check_parallel_settings(settings, input_files)?;
+
+    // Row groups to convert, as (file index, row group index).
+    let mut row_groups = Vec::new();
+    for (file_idx, reader) in readers.iter().enumerate() {
+        let num_row_groups = reader.num_row_groups();
+        let indices = match settings.row_groups {
+            Some(ref indices) => indices.clone(),
+            None => (0..num_row_groups).collect(),
+        };
+        if let Some(i) = indices.iter().find(|&&i| i >= num_row_groups) {
+            return Err(format!(
+                "Row group index {} is out of range, the file has {} row groups",
+                i, num_row_groups
+            )
+            .into());
+        }
+        row_groups.extend(indices.into_iter().map(|i| (file_idx, i)));
+    }
+
+    let settings = Arc::new(settings.clone());
+    let input_files = Arc::new(
+        input_files
+            .iter()
+            .map(|input_file| input_file.to_string())
+            .collect::<Vec<_>>(),
+    );
+    let row_groups = Arc::new(row_groups);
+    let mut receivers = Vec::with_capacity(settings.threads);
+    let mut workers = Vec::with_capacity(settings.threads);
+    for worker in 0..settings.threads {
+        let (sender, receiver) = sync_channel(1);
+        let settings = settings.clone();
+        let input_files = input_files.clone();
+        let row_groups = row_groups.clone();
+        let schema = schema.clone();
+        let fields = fields.clone();
+        workers.push(thread::spawn(move || {
+            convert_row_groups(
+                &settings,
+                &input_files,
+                &row_groups,
+                worker,
+                schema,
+                &fields,
+                sender,
+            )
+        }));
+        receivers.push(receiver);
+    }
+
+    Ok(ParallelRecords {
+        receivers,
+        workers,
+        num_row_groups: row_groups.len(),
+        next_row_group: 0,
+        current: Vec::new().into_iter(),
+    })
+}
+
+fn check_parallel_settings(
+    settings: &Settings,
+    input_files: &[&str],
+) -> Result<(), Box<dyn Error>> {
+    if input_files.contains(&STDIN_INPUT) {
+        return Err("--threads requires input files, STDIN can't be read in parallel".into());
+    }
+    if input_files.iter().any(|input_file| is_url(input_file)) {
+        return Err("--threads requires input files, URLs can't be read in parallel".into());
+    }
+    let unsupported = [
+        (
+            "output formats other than jsonl, json-array and kusto-multijson",
+            !matches!(
+                settings.format,
+                OutputFormat::Jsonl | OutputFormat::JsonArray | OutputFormat::KustoMultiJson
+            ),
+        ),
+        ("--limit", settings.limit.is_some()),
+        ("--skip", settings.skip > 0),
+        ("--tail", settings.tail.is_some()),
+        ("--resume-from", settings.resume_from > 0),
+        ("--sample", settings.sample.is_some()),
+        ("--progress", settings.progress),
+    ];
+    match unsupported.iter().find(|(_, present)| *present) {
+        Some((option, _)) => Err(format!("{} is not supported with --threads", option).into()),
+        None => Ok(()),
+    }
+}
+
+/// Worker converting every `threads`-th row group, starting with the `worker`-th one.
+/// Every worker opens its own file readers, same as the single-threaded conversion
+/// (with --mmap and the encrypted footer check).
+fn convert_row_groups(
+    settings: &Settings,
+    input_files: &[String],
+    row_groups: &[(usize, usize)],
+    worker: usize,
+    schema: Option<SchemaType>,
+    fields: &[TypePtr],
+    sender: SyncSender<RowGroupRecords>,
+) {
+    let mut readers: Vec<Option<Box<dyn FileReader>>> = input_files.iter().map(|_| None).collect();
+    for &(file_idx, row_group_idx) in row_groups.iter().skip(worker).step_by(settings.threads) {
+        if readers[file_idx].is_none() {
+            match open_input(settings, &input_files[file_idx]) {
+                Ok(reader) => readers[file_idx] = Some(reader),
+                Err(e) => {
+                    let _ = sender.send(Err(WorkerError::new(e)));
+                    return;
+                }
+            }
+        }
+        let reader = readers[file_idx].as_ref().expect("reader is opened");
+
+        debug!(
+            "Worker {} reading row group {} of {}",
+            worker, row_group_idx, input_files[file_idx]
+        );
+        let records = row_group_records(
+            settings,
+            &input_files[file_idx],
+            reader.as_ref(),
+            row_group_idx,
+            schema.clone(),
+            fields,
+        )
+        .map_err(WorkerError::new);
+
+        let failed = records.is_err();
+        // Receiver is dropped if the conversion has stopped early.
+        if sender.send(records).is_err() || failed {
+            return;
+        }
+    }
+}
+
+fn row_group_records(
+    settings: &Settings,
+    input_file: &str,
+    reader: &dyn FileReader,
+    row_group_idx: usize,
+    schema: Option<SchemaType>,
+    fields: &[TypePtr],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let rows = reader.get_row_group(row_group_idx)?.get_row_iter(schema)?;
+    let mut records = Vec::new();
+    // Index of the first row of the row group in the file.
+    let first_row = reader.metadata().row_groups()[..row_group_idx]
+        .iter()
+        .map(|row_group| row_group.num_rows() as usize)
+        .sum::<usize>();
+    let rows = rows.enumerate().filter(|(_, row)| {
+        settings
+            .filter
+            .as_ref()
+            .map_or(true, |filter| row_matches(filter, row))
+    });
+    for (row_idx, row) in rows {
+        let row_idx = first_row + row_idx;
+        let values = top_level_row_to_value(settings, fields, &row)
+            .map(|value| add_synthetic_columns(settings, value, Some(input_file), row_idx))
+            .and_then(|value| explode_row(settings, value))
+            .map_err(|e| RowError::new(Some(input_file), row_idx, e))?;
+        for value in values {
+            if !is_skipped_empty_row(settings, &value) {
+                records.push(value_to_json(settings, value)?);
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Iterator over records received from the workers, in row group order.
+///
+/// Workers are joined once all row groups are received, or the conversion stops,
+/// so that a panic of a worker is reported instead of being lost with its thread.
+pub(crate) struct ParallelRecords {
+    receivers: Vec<Receiver<RowGroupRecords>>,
+    workers: Vec<JoinHandle<()>>,
+    num_row_groups: usize,
+    next_row_group: usize,
+    current: std::vec::IntoIter<String>,
+}
+
+impl Iterator for ParallelRecords {
+    type Item = Result<String, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.current.next() {
+                return Some(Ok(record));
+            }
+            if self.next_row_group == self.num_row_groups {
+                return self
+                    .join_workers()
+                    .map(|panic| Err(format!("Conversion worker panicked: {}", panic).into()));
+            }
+            let receiver = &self.receivers[self.next_row_group % self.receivers.len()];
+            self.next_row_group += 1;
+            match receiver.recv() {
+                Ok(Ok(records)) => self.current = records.into_iter(),
+                Ok(Err(e)) => {
+                    self.next_row_group = self.num_row_groups;
+                    return Some(Err(e.into_error()));
+                }
+                Err(_) => {
+                    self.next_row_group = self.num_row_groups;
+                    let message = match self.join_workers() {
+                        Some(panic) => format!("Conversion worker panicked: {}", panic),
+                        None => "Conversion worker has stopped unexpectedly".to_owned(),
+                    };
+                    return Some(Err(message.into()));
+                }
+            }
+        }
+    }
+}
+
+impl ParallelRecords {
+    /// Stops and joins the workers, returning the message of the first panic, if any.
+    fn join_workers(&mut self) -> Option<String> {
+        // Workers waiting to send records stop once their receiver is dropped.
+        self.receivers.clear();
+        let panics = self
+            .workers
+            .drain(..)
+            .filter_map(|worker| worker.join().err())
+            .map(panic_message)
+            .collect::<Vec<_>>();
+        panics.into_iter().next()
+    }
+}
+
+impl Drop for ParallelRecords {
+    fn drop(&mut self) {
+        if let Some(panic) = self.join_workers() {
+            warn!("Conversion worker panicked: {}", panic);
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_owned(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{write_batch, Fixture};
+    use parquet::data_type::Int32Type;
+
+    fn row_groups_fixture(name: &str) -> Fixture {
+        Fixture::new(
+            name,
+            "message schema {
+                REQUIRED INT32 id;
+            }",
+            5,
+            |row_group, _, writer| {
+                let first = row_group as i32 * 3;
+                write_batch::<Int32Type>(writer, &[first, first + 1, first + 2], None, None);
+            },
+        )
+    }
+
+    #[test]
+    fn parallel_output_matches_single_threaded() {
+        let fixture = row_groups_fixture("parallel-row-groups");
+        let single = fixture.convert(&Settings::default()).unwrap();
+        let settings = Settings {
+            threads: 2,
+            ..Settings::default()
+        };
+        assert_eq!(fixture.convert(&settings).unwrap(), single);
+        assert_eq!(single.lines().count(), 15);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn parallel_workers_read_with_mmap() {
+        let fixture = row_groups_fixture("parallel-mmap");
+        let single = fixture.convert(&Settings::default()).unwrap();
+        let settings = Settings {
+            threads: 2,
+            mmap: true,
+            ..Settings::default()
+        };
+        assert_eq!(fixture.convert(&settings).unwrap(), single);
+    }
+
+    #[test]
+    fn parallel_row_error_reports_file_and_row() {
+        let fixture = Fixture::new(
+            "parallel-row-error",
+            "message schema {
+                REQUIRED INT32 t (TIME_MILLIS);
+            }",
+            2,
+            |row_group, _, writer| {
+                let values = match row_group {
+                    0 => [0, 1, 2],
+                    _ => [3, 24 * 60 * 60 * 1000, 5],
+                };
+                write_batch::<Int32Type>(writer, &values, None, None);
+            },
+        );
+        let settings = Settings {
+            threads: 2,
+            ..Settings::default()
+        };
+        match fixture.convert(&settings) {
+            Err(ConvertError::Row(e)) => {
+                assert_eq!(e.row, 4);
+                assert_eq!(e.file.as_deref(), Some(fixture.path()));
+            }
+            result => panic!("expected a row error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn worker_panic_is_reported() {
+        let (sender, receiver) = sync_channel::<RowGroupRecords>(1);
+        let worker = thread::spawn(move || {
+            let _sender = sender;
+            panic!("row group is corrupt");
+        });
+        let mut records = ParallelRecords {
+            receivers: vec![receiver],
+            workers: vec![worker],
+            num_row_groups: 1,
+            next_row_group: 0,
+            current: Vec::new().into_iter(),
+        };
+        assert_eq!(
+            records.next().unwrap().unwrap_err().to_string(),
+            "Conversion worker panicked: row group is corrupt"
+        );
+        assert!(records.next().is_none());
+    }
+}
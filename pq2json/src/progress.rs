@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+/// Minimal interval between progress reports.
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Number of rows between clock checks, to keep the per-row overhead negligible.
+const CHECK_EVERY_ROWS: usize = 1024;
+
+/// Iterator adapter reporting the number of rows passed through to STDERR.
+pub struct Progress<I> {
+    inner: I,
+    rows: usize,
+    total_rows: Option<usize>,
+    last_report: Instant,
+    done: bool,
+}
+
+impl<I> Progress<I> {
+    pub fn new(inner: I, total_rows: Option<usize>) -> Self {
+        Progress {
+            inner,
+            rows: 0,
+            total_rows,
+            last_report: Instant::now(),
+            done: false,
+        }
+    }
+
+    fn report(&mut self) {
+        match self.total_rows {
+            Some(total_rows) if total_rows > 0 => eprint!(
+                "\r{} / {} rows ({:.1}%)",
+                self.rows,
+                total_rows,
+                self.rows as f64 * 100.0 / total_rows as f64
+            ),
+            _ => eprint!("\r{} rows", self.rows),
+        }
+        self.last_report = Instant::now();
+    }
+}
+
+impl<I: Iterator> Iterator for Progress<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next();
+        match item {
+            Some(_) => {
+                self.rows += 1;
+                if self.rows % CHECK_EVERY_ROWS == 0
+                    && self.last_report.elapsed() >= REPORT_INTERVAL
+                {
+                    self.report();
+                }
+            }
+            None if !self.done => {
+                self.done = true;
+                self.report();
+                eprintln!();
+            }
+            None => (),
+        }
+        item
+    }
+}
@@ -1,36 +1,45 @@
+use std::collections::HashMap;
 use std::error::Error;
-use std::fs::File;
-use std::path::Path;
+use std::fs;
 
 use itertools::Itertools;
-use parquet::basic::{LogicalType, Type as PhysicalType};
-use parquet::file::reader::{FileReader, SerializedFileReader};
+use log::{info, warn};
+use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+use parquet::column::reader::{ColumnReader, ColumnReaderImpl};
+use parquet::data_type::DataType;
+use parquet::errors::Result as ParquetResult;
+use parquet::file::metadata::ColumnChunkMetaData;
+use parquet::file::statistics::Statistics;
 use parquet::schema::printer::{print_file_metadata, print_parquet_metadata};
-use parquet::schema::types::Type;
-use serde_json::Value;
+use parquet::schema::types::{Type, TypePtr};
+use serde_json::{Number, Value};
+
+use crate::converter::column_alias;
+use crate::error::ConvertError;
+use crate::input::open_reader;
+use crate::settings::{BytesEncoding, FloatSpecial, Settings, TimestampRendering};
 
 /// Prints Parquet file schema information
 ///
 /// Arguments:
 ///
-/// * `input_file` - Parquet file path
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
 ///
-pub fn print_schema(input_file: &str) -> Result<(), Box<dyn Error>> {
-    let file = File::open(&Path::new(input_file))?;
-    let reader = SerializedFileReader::new(file)?;
+pub fn print_schema(input_file: &str) -> Result<(), ConvertError> {
+    let reader = open_reader(input_file)?;
     let meta = reader.metadata();
     let mut output = Vec::new();
     print_parquet_metadata(&mut output, &meta);
     println!("\n\nParquet metadata");
     println!("=================================================");
-    println!("{}", String::from_utf8(output)?);
+    println!("{}", String::from_utf8_lossy(&output));
 
     let mut output = Vec::new();
     let file_meta = reader.metadata().file_metadata();
     print_file_metadata(&mut output, &file_meta);
     println!("\n\nFile metadata");
     println!("=================================================");
-    println!("{}", String::from_utf8(output)?);
+    println!("{}", String::from_utf8_lossy(&output));
     Ok(())
 }
 
@@ -38,21 +47,24 @@ pub fn print_schema(input_file: &str) -> Result<(), Box<dyn Error>> {
 ///
 /// Arguments:
 ///
-/// * `input_file` - Parquet file path
+/// * `settings` - Converter settings, of which --type-override and --uuid-columns
+///   set the types of top-level columns
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
 ///
-pub fn print_csl_schema(input_file: &str) -> Result<(), Box<dyn Error>> {
-    // Instead of dealing with logical types translation, we just get the first
-    // row, and print it's schema:
-    let file = File::open(&Path::new(input_file))?;
-    let reader = SerializedFileReader::new(file)?;
+pub fn print_csl_schema(settings: &Settings, input_file: &str) -> Result<(), ConvertError> {
+    // Types are inferred from the footer schema, no data pages are read.
+    let reader = open_reader(input_file)?;
     let file_meta = reader.metadata().file_metadata();
     let schema_desc = file_meta.schema_descr();
 
     let fields = match schema_desc.root_schema() {
-        &Type::GroupType { ref fields, .. } => fields
-            .iter()
-            .map(|field| field_csl_schema(field))
-            .collect::<Vec<(&str, &str)>>(),
+        &Type::GroupType { ref fields, .. } => {
+            warn_unknown_overrides(&settings.type_overrides, fields);
+            fields
+                .iter()
+                .map(|field| overridden_csl_schema(settings, field))
+                .collect::<Vec<(&str, &str)>>()
+        }
         _ => panic!("root schema is expected to be of group type!"),
     };
 
@@ -71,8 +83,127 @@ pub fn print_csl_schema(input_file: &str) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Prints Kusto `.create table` command with the schema of specified Parquet file.
+///
+/// Arguments:
+///
+/// * `settings` - Converter settings, of which --type-override and --uuid-columns
+///   set the types of top-level columns
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
+/// * `table_name` - Kusto table name
+///
+pub fn print_create_table(
+    settings: &Settings,
+    input_file: &str,
+    table_name: &str,
+) -> Result<(), ConvertError> {
+    let reader = open_reader(input_file)?;
+    let fields = reader.metadata().file_metadata().schema().get_fields();
+    warn_unknown_overrides(&settings.type_overrides, fields);
+    let columns = fields
+        .iter()
+        .map(|field| {
+            let (field_name, field_type) = overridden_csl_schema(settings, field);
+            format!("{}:{}", kusto_identifier(field_name), field_type)
+        })
+        .join(", ");
+    println!(
+        ".create table {} ({})",
+        kusto_identifier(table_name),
+        columns
+    );
+    Ok(())
+}
+
+/// Common Kusto keywords, which can't be used as unquoted entity names.
+const KUSTO_KEYWORDS: &[&str] = &[
+    "and",
+    "as",
+    "by",
+    "contains",
+    "count",
+    "distinct",
+    "extend",
+    "false",
+    "has",
+    "in",
+    "join",
+    "let",
+    "limit",
+    "not",
+    "on",
+    "or",
+    "order",
+    "project",
+    "sort",
+    "summarize",
+    "table",
+    "take",
+    "to",
+    "top",
+    "true",
+    "union",
+    "where",
+    "with",
+];
+
+/// Quotes Kusto entity name as `['name']`, unless it's a plain identifier.
+fn kusto_identifier(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KUSTO_KEYWORDS.contains(&name);
+    if plain {
+        name.to_owned()
+    } else {
+        format!("['{}']", name.replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+}
+
+/// Name and Kusto type of a top-level field, as set by --type-override, `guid` for
+/// binary --uuid-columns (rendered as GUID strings), or inferred.
+fn overridden_csl_schema<'a>(settings: &'a Settings, field_type: &'a Type) -> (&'a str, &'a str) {
+    let (name, csl_type) = field_csl_schema(field_type);
+    if let Some(csl_type) = settings.type_overrides.get(name) {
+        return (name, csl_type.as_str());
+    }
+    let binary = match field_type {
+        Type::PrimitiveType { physical_type, .. } => {
+            *physical_type == PhysicalType::BYTE_ARRAY
+                || *physical_type == PhysicalType::FIXED_LEN_BYTE_ARRAY
+        }
+        Type::GroupType { .. } => false,
+    };
+    if binary && settings.uuid_columns.iter().any(|column| column == name) {
+        return (name, "guid");
+    }
+    (name, csl_type)
+}
+
+fn warn_unknown_overrides(type_overrides: &HashMap<String, String>, fields: &[TypePtr]) {
+    let unknown = type_overrides
+        .keys()
+        .filter(|column| !fields.iter().any(|field| field.name() == column.as_str()))
+        .sorted()
+        .join(", ");
+    if !unknown.is_empty() {
+        warn!(
+            "Type overrides of columns not found in the file: {}",
+            unknown
+        );
+    }
+}
+
 fn field_csl_schema(field_type: &Type) -> (&str, &str) {
     match field_type {
+        // Legacy repeated primitive (not wrapped in a LIST group) is converted to an array.
+        Type::PrimitiveType { ref basic_info, .. }
+            if basic_info.has_repetition() && basic_info.repetition() == Repetition::REPEATED =>
+        {
+            (basic_info.name(), "dynamic")
+        }
         Type::PrimitiveType {
             ref basic_info,
             physical_type,
@@ -81,7 +212,8 @@ fn field_csl_schema(field_type: &Type) -> (&str, &str) {
             let csl_type = match physical_type {
                 PhysicalType::BOOLEAN => "bool",
                 PhysicalType::BYTE_ARRAY => match basic_info.logical_type() {
-                    LogicalType::UTF8 | LogicalType::ENUM | LogicalType::JSON => "string",
+                    LogicalType::UTF8 | LogicalType::ENUM => "string",
+                    LogicalType::JSON => "dynamic",
                     LogicalType::DECIMAL => "decimal",
                     _ => "dynamic",
                 },
@@ -92,11 +224,13 @@ fn field_csl_schema(field_type: &Type) -> (&str, &str) {
                 PhysicalType::DOUBLE | PhysicalType::FLOAT => "real",
                 PhysicalType::INT32 => match basic_info.logical_type() {
                     LogicalType::DATE => "datetime",
+                    LogicalType::TIME_MILLIS => "timespan",
                     LogicalType::DECIMAL => "real",
                     _ => "int",
                 },
                 PhysicalType::INT64 => match basic_info.logical_type() {
                     LogicalType::TIMESTAMP_MILLIS | LogicalType::TIMESTAMP_MICROS => "datetime",
+                    LogicalType::TIME_MICROS => "timespan",
                     LogicalType::DECIMAL => "real",
                     _ => "long",
                 },
@@ -108,23 +242,457 @@ fn field_csl_schema(field_type: &Type) -> (&str, &str) {
     }
 }
 
+/// Writes Kusto JSON ingestion mapping of the top-level `fields` (as named on output,
+/// see --columns aliases), typed same as `print_csl_schema` (including --type-override
+/// and --uuid-columns).
+pub(crate) fn write_ingestion_mapping(
+    settings: &Settings,
+    fields: &[TypePtr],
+    mapping_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    if settings.flatten {
+        return Err("--mapping-out is not supported with --flatten".into());
+    }
+    let mut mapping = fields
+        .iter()
+        .map(|field| {
+            let (name, csl_type) = overridden_csl_schema(settings, field);
+            let name = settings
+                .column_aliases
+                .get(name)
+                .map_or(name, |alias| alias.as_str());
+            column_mapping(name, csl_type)
+        })
+        .collect_vec();
+    if let Some(ref rownum_column) = settings.rownum_column {
+        mapping.push(column_mapping(rownum_column, "long"));
+    }
+    if let Some(ref filename_column) = settings.filename_column {
+        mapping.push(column_mapping(filename_column, "string"));
+    }
+    let mapping = Value::Array(mapping);
+    info!("Writing ingestion mapping to {}", mapping_file);
+    fs::write(mapping_file, serde_json::to_string_pretty(&mapping)?)?;
+    Ok(())
+}
+
+fn column_mapping(name: &str, csl_type: &str) -> Value {
+    let mut properties = serde_json::Map::with_capacity(1);
+    properties.insert(
+        String::from("Path"),
+        Value::String(format!("$['{}']", name.replace('\'', "\\'"))),
+    );
+    let mut map = serde_json::Map::with_capacity(3);
+    map.insert(String::from("column"), Value::String(name.to_string()));
+    map.insert(
+        String::from("datatype"),
+        Value::String(csl_type.to_string()),
+    );
+    map.insert(String::from("Properties"), Value::Object(properties));
+    Value::Object(map)
+}
+
+/// Prints full schema tree of specified Parquet file as nested JSON, including
+/// nested struct fields and list and map element types.
+///
+/// Arguments:
+///
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
+///
+pub fn print_json_schema(input_file: &str) -> Result<(), ConvertError> {
+    let reader = open_reader(input_file)?;
+    let file_meta = reader.metadata().file_metadata();
+    let schema = field_json_schema(file_meta.schema_descr().root_schema());
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+fn field_json_schema(field_type: &Type) -> Value {
+    let basic_info = field_type.get_basic_info();
+    let mut map = serde_json::Map::new();
+    map.insert(
+        String::from("name"),
+        Value::String(basic_info.name().to_owned()),
+    );
+    if basic_info.has_repetition() {
+        map.insert(
+            String::from("repetition"),
+            Value::String(basic_info.repetition().to_string()),
+        );
+    }
+    if basic_info.logical_type() != LogicalType::NONE {
+        map.insert(
+            String::from("logicalType"),
+            Value::String(basic_info.logical_type().to_string()),
+        );
+    }
+    match field_type {
+        Type::PrimitiveType {
+            physical_type,
+            type_length,
+            scale,
+            precision,
+            ..
+        } => {
+            map.insert(
+                String::from("type"),
+                Value::String(physical_type.to_string()),
+            );
+            if *physical_type == PhysicalType::FIXED_LEN_BYTE_ARRAY {
+                map.insert(String::from("length"), Value::Number((*type_length).into()));
+            }
+            if basic_info.logical_type() == LogicalType::DECIMAL {
+                map.insert(
+                    String::from("precision"),
+                    Value::Number((*precision).into()),
+                );
+                map.insert(String::from("scale"), Value::Number((*scale).into()));
+            }
+        }
+        Type::GroupType { ref fields, .. } => {
+            map.insert(String::from("type"), Value::String(String::from("group")));
+            map.insert(
+                String::from("fields"),
+                Value::Array(
+                    fields
+                        .iter()
+                        .map(|field| field_json_schema(field))
+                        .collect_vec(),
+                ),
+            );
+        }
+    }
+    Value::Object(map)
+}
+
+/// Prints JSON Schema (draft-07) of the records converted from a specified Parquet file:
+/// optional fields are nullable, lists and repeated fields are arrays, structs and maps
+/// are objects. Scalar types follow the rendering settings, e.g. timestamps are integers
+/// (ticks) unless rendered as ISO 8601 strings. Columns are named after their aliases and
+/// followed by the rownum and filename columns, if added. Column selection and --flatten
+/// are not applied, every field of the file is described.
+///
+/// Arguments:
+///
+/// * `settings` - Converter settings
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
+///
+pub fn print_record_json_schema(settings: &Settings, input_file: &str) -> Result<(), ConvertError> {
+    let reader = open_reader(input_file)?;
+    let file_meta = reader.metadata().file_metadata();
+    let mut schema = serde_json::Map::new();
+    schema.insert(
+        String::from("$schema"),
+        Value::String(String::from("http://json-schema.org/draft-07/schema#")),
+    );
+    schema.extend(record_schema(
+        settings,
+        file_meta.schema_descr().root_schema().get_fields(),
+    ));
+    println!("{}", serde_json::to_string_pretty(&Value::Object(schema))?);
+    Ok(())
+}
+
+/// JSON Schema of the records with the `fields` top-level fields, named after their
+/// aliases, followed by the rownum and filename columns.
+fn record_schema(settings: &Settings, fields: &[TypePtr]) -> serde_json::Map<String, Value> {
+    let mut schema = struct_record_schema(settings, fields, true);
+    let synthetic_columns = settings
+        .rownum_column
+        .iter()
+        .map(|column| (column, "integer"))
+        .chain(
+            settings
+                .filename_column
+                .iter()
+                .map(|column| (column, "string")),
+        )
+        .collect_vec();
+    for (column, json_type) in synthetic_columns {
+        if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+            properties.insert(column.to_owned(), typed_record_schema(json_type, None));
+        }
+        let name = Value::String(column.to_owned());
+        if let Value::Array(required) = schema
+            .entry("required")
+            .or_insert_with(|| Value::Array(Vec::new()))
+        {
+            if !required.contains(&name) {
+                required.push(name);
+            }
+        }
+    }
+    schema
+}
+
+fn struct_record_schema(
+    settings: &Settings,
+    fields: &[TypePtr],
+    top_level: bool,
+) -> serde_json::Map<String, Value> {
+    let mut properties = serde_json::Map::new();
+    let mut required = Vec::new();
+    for field in fields {
+        let basic_info = field.get_basic_info();
+        let name = if top_level {
+            column_alias(settings, basic_info.name())
+        } else {
+            basic_info.name()
+        };
+        if basic_info.has_repetition() && basic_info.repetition() == Repetition::REQUIRED {
+            required.push(Value::String(name.to_owned()));
+        }
+        properties.insert(
+            name.to_owned(),
+            field_record_schema(settings, field, top_level),
+        );
+    }
+    let mut map = serde_json::Map::new();
+    map.insert(String::from("type"), Value::String(String::from("object")));
+    map.insert(String::from("properties"), Value::Object(properties));
+    if !required.is_empty() {
+        map.insert(String::from("required"), Value::Array(required));
+    }
+    map
+}
+
+/// JSON Schema of a field, according to its repetition.
+fn field_record_schema(settings: &Settings, field_type: &Type, top_level: bool) -> Value {
+    let basic_info = field_type.get_basic_info();
+    let schema = value_record_schema(settings, field_type, top_level);
+    if !basic_info.has_repetition() {
+        return schema;
+    }
+    match basic_info.repetition() {
+        // Legacy repeated field (not wrapped in a LIST group) is converted to an array.
+        Repetition::REPEATED => array_record_schema(schema),
+        Repetition::OPTIONAL => nullable_record_schema(schema),
+        Repetition::REQUIRED => schema,
+    }
+}
+
+/// JSON Schema of a field value, regardless of its repetition. Top-level fields may be
+/// rendered differently, e.g. as --uuid-columns.
+fn value_record_schema(settings: &Settings, field_type: &Type, top_level: bool) -> Value {
+    let basic_info = field_type.get_basic_info();
+    let logical_type = basic_info.logical_type();
+    let physical_type = match field_type {
+        Type::PrimitiveType { physical_type, .. } => *physical_type,
+        Type::GroupType { ref fields, .. } => {
+            return match logical_type {
+                LogicalType::LIST => match fields.first() {
+                    Some(repeated) => array_record_schema(list_element_record_schema(
+                        settings,
+                        basic_info.name(),
+                        repeated,
+                    )),
+                    None => typed_record_schema("array", None),
+                },
+                LogicalType::MAP | LogicalType::MAP_KEY_VALUE => {
+                    let mut map = serde_json::Map::new();
+                    map.insert(String::from("type"), Value::String(String::from("object")));
+                    // Keys are rendered as strings, the value is the second key_value field.
+                    if let Some(value) = fields
+                        .first()
+                        .filter(|key_value| key_value.is_group())
+                        .and_then(|key_value| key_value.get_fields().get(1))
+                    {
+                        map.insert(
+                            String::from("additionalProperties"),
+                            field_record_schema(settings, value, false),
+                        );
+                    }
+                    Value::Object(map)
+                }
+                _ => Value::Object(struct_record_schema(settings, fields, false)),
+            };
+        }
+    };
+    match physical_type {
+        PhysicalType::BOOLEAN => typed_record_schema("boolean", None),
+        PhysicalType::INT32 => match logical_type {
+            LogicalType::DATE => date_record_schema(settings),
+            // Nested TIME values are converted as plain numbers.
+            LogicalType::TIME_MILLIS if top_level => time_record_schema(settings),
+            LogicalType::DECIMAL => decimal_record_schema(settings),
+            _ => typed_record_schema("integer", None),
+        },
+        PhysicalType::INT64 => match logical_type {
+            LogicalType::TIMESTAMP_MILLIS | LogicalType::TIMESTAMP_MICROS => {
+                timestamp_record_schema(settings)
+            }
+            LogicalType::TIME_MICROS if top_level => time_record_schema(settings),
+            LogicalType::DECIMAL => decimal_record_schema(settings),
+            _ if settings.long_as_string => multi_typed_record_schema(&["integer", "string"]),
+            _ => typed_record_schema("integer", None),
+        },
+        PhysicalType::INT96 => timestamp_record_schema(settings),
+        PhysicalType::FLOAT | PhysicalType::DOUBLE => match settings.float_special {
+            // NaN and infinite values.
+            FloatSpecial::Null => multi_typed_record_schema(&["number", "null"]),
+            FloatSpecial::String => multi_typed_record_schema(&["number", "string"]),
+        },
+        PhysicalType::BYTE_ARRAY | PhysicalType::FIXED_LEN_BYTE_ARRAY => match logical_type {
+            LogicalType::UTF8 | LogicalType::ENUM => typed_record_schema("string", None),
+            // Top-level JSON columns are parsed, nested ones are kept as strings.
+            LogicalType::JSON if top_level => Value::Object(serde_json::Map::new()),
+            LogicalType::JSON => typed_record_schema("string", None),
+            LogicalType::DECIMAL => decimal_record_schema(settings),
+            _ if top_level
+                && settings
+                    .uuid_columns
+                    .iter()
+                    .any(|column| column == basic_info.name()) =>
+            {
+                typed_record_schema("string", Some("uuid"))
+            }
+            _ => bytes_record_schema(settings),
+        },
+    }
+}
+
+/// Element of a LIST group, following the backward compatibility rules of the format:
+/// the repeated field is the element itself if it's primitive, a struct of several fields,
+/// or named `array` or `<list>_tuple`, otherwise its only field is.
+fn list_element_record_schema(settings: &Settings, list_name: &str, repeated: &Type) -> Value {
+    match repeated {
+        Type::GroupType { ref fields, .. }
+            if fields.len() == 1
+                && repeated.name() != "array"
+                && repeated.name() != format!("{}_tuple", list_name) =>
+        {
+            field_record_schema(settings, &fields[0], false)
+        }
+        _ => value_record_schema(settings, repeated, false),
+    }
+}
+
+fn timestamp_record_schema(settings: &Settings) -> Value {
+    match settings.timestamp_rendering {
+        TimestampRendering::Ticks if settings.ticks_as_string => {
+            typed_record_schema("string", None)
+        }
+        TimestampRendering::Ticks | TimestampRendering::UnixMs => {
+            typed_record_schema("integer", None)
+        }
+        // Custom --timestamp-format may not be RFC 3339.
+        TimestampRendering::IsoStr if settings.timestamp_format.is_some() => {
+            typed_record_schema("string", None)
+        }
+        TimestampRendering::IsoStr => typed_record_schema("string", Some("date-time")),
+    }
+}
+
+/// Dates are rendered as the timestamp of their midnight unless rendered as ISO 8601 strings.
+fn date_record_schema(settings: &Settings) -> Value {
+    match settings.timestamp_rendering {
+        TimestampRendering::IsoStr => typed_record_schema("string", Some("date")),
+        _ => timestamp_record_schema(settings),
+    }
+}
+
+fn time_record_schema(settings: &Settings) -> Value {
+    if settings.time_as_number {
+        typed_record_schema("integer", None)
+    } else {
+        typed_record_schema("string", Some("time"))
+    }
+}
+
+fn decimal_record_schema(settings: &Settings) -> Value {
+    if settings.decimal_as_number {
+        typed_record_schema("number", None)
+    } else {
+        typed_record_schema("string", Some("decimal"))
+    }
+}
+
+fn bytes_record_schema(settings: &Settings) -> Value {
+    match (settings.bytes_encoding, settings.bytes_as_string) {
+        // With --bytes-as-string valid UTF-8 is rendered as a string, other values encoded.
+        (BytesEncoding::Array, true) => Value::Object(serde_json::Map::new()),
+        (BytesEncoding::Array, false) => array_record_schema(typed_record_schema("integer", None)),
+        (BytesEncoding::Base64, false) => {
+            let mut map = serde_json::Map::new();
+            map.insert(String::from("type"), Value::String(String::from("string")));
+            map.insert(
+                String::from("contentEncoding"),
+                Value::String(String::from("base64")),
+            );
+            Value::Object(map)
+        }
+        (BytesEncoding::Base64, true) | (BytesEncoding::Hex, _) => {
+            typed_record_schema("string", None)
+        }
+    }
+}
+
+fn typed_record_schema(json_type: &str, format: Option<&str>) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert(String::from("type"), Value::String(json_type.to_owned()));
+    if let Some(format) = format {
+        map.insert(String::from("format"), Value::String(format.to_owned()));
+    }
+    Value::Object(map)
+}
+
+fn multi_typed_record_schema(json_types: &[&str]) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert(
+        String::from("type"),
+        Value::Array(
+            json_types
+                .iter()
+                .map(|json_type| Value::String((*json_type).to_owned()))
+                .collect(),
+        ),
+    );
+    Value::Object(map)
+}
+
+fn array_record_schema(items: Value) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert(String::from("type"), Value::String(String::from("array")));
+    map.insert(String::from("items"), items);
+    Value::Object(map)
+}
+
+/// Adds null to the types of a schema (a schema without type accepts null already).
+fn nullable_record_schema(schema: Value) -> Value {
+    let mut map = match schema {
+        Value::Object(map) => map,
+        schema => return schema,
+    };
+    let null = Value::String(String::from("null"));
+    match map.get_mut("type") {
+        Some(Value::Array(json_types)) => {
+            if !json_types.contains(&null) {
+                json_types.push(null);
+            }
+        }
+        Some(json_type) => *json_type = Value::Array(vec![json_type.take(), null]),
+        None => (),
+    }
+    Value::Object(map)
+}
+
 /// Prints limited row groups metadata of a specified Parquet file as JSON,
-/// for each row group its size in bytes and the number of rows.
+/// for each row group its size in bytes, the number of rows, and per column chunk
+/// its compression codec, encodings and compressed and uncompressed sizes.
 ///
 /// Arguments:
 ///
-/// * `input_file` - Parquet file path
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
 ///
-pub fn print_row_groups_metadata(input_file: &str) -> Result<(), Box<dyn Error>> {
-    let file = File::open(&Path::new(input_file))?;
-    let reader = SerializedFileReader::new(file)?;
+pub fn print_row_groups_metadata(input_file: &str) -> Result<(), ConvertError> {
+    let reader = open_reader(input_file)?;
     let row_groups = Value::Array(
         reader
             .metadata()
             .row_groups()
             .iter()
             .map(|row_group_metadata| {
-                let mut map = serde_json::Map::with_capacity(2);
+                let mut map = serde_json::Map::with_capacity(3);
                 map.insert(
                     String::from("numberOfRows"),
                     Value::String(row_group_metadata.num_rows().to_string()),
@@ -133,6 +701,89 @@ pub fn print_row_groups_metadata(input_file: &str) -> Result<(), Box<dyn Error>>
                     String::from("totalByteSize"),
                     Value::String(row_group_metadata.total_byte_size().to_string()),
                 );
+                map.insert(
+                    String::from("columns"),
+                    Value::Array(
+                        row_group_metadata
+                            .columns()
+                            .iter()
+                            .map(column_chunk_metadata)
+                            .collect_vec(),
+                    ),
+                );
+                Value::Object(map)
+            })
+            .collect_vec(),
+    );
+
+    println!("{}", serde_json::to_string(&row_groups)?);
+    Ok(())
+}
+
+fn column_chunk_metadata(column_metadata: &ColumnChunkMetaData) -> Value {
+    let mut map = serde_json::Map::with_capacity(5);
+    map.insert(
+        String::from("name"),
+        Value::String(column_metadata.column_path().string()),
+    );
+    map.insert(
+        String::from("compression"),
+        Value::String(column_metadata.compression().to_string()),
+    );
+    map.insert(
+        String::from("encodings"),
+        Value::Array(
+            column_metadata
+                .encodings()
+                .iter()
+                .map(|encoding| Value::String(encoding.to_string()))
+                .collect(),
+        ),
+    );
+    map.insert(
+        String::from("compressedSize"),
+        Value::String(column_metadata.compressed_size().to_string()),
+    );
+    map.insert(
+        String::from("uncompressedSize"),
+        Value::String(column_metadata.uncompressed_size().to_string()),
+    );
+    Value::Object(map)
+}
+
+/// Prints column chunk statistics of a specified Parquet file as JSON, for each row group
+/// and column its min and max, null count and distinct count (null if not recorded).
+/// Min and max are rendered by physical type, e.g. timestamps as integers.
+///
+/// Arguments:
+///
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
+///
+pub fn print_statistics(input_file: &str) -> Result<(), ConvertError> {
+    let reader = open_reader(input_file)?;
+    let row_groups = Value::Array(
+        reader
+            .metadata()
+            .row_groups()
+            .iter()
+            .enumerate()
+            .map(|(i, row_group_metadata)| {
+                let mut map = serde_json::Map::with_capacity(3);
+                map.insert(String::from("rowGroup"), Value::Number(i.into()));
+                map.insert(
+                    String::from("numberOfRows"),
+                    Value::String(row_group_metadata.num_rows().to_string()),
+                );
+                map.insert(
+                    String::from("columns"),
+                    Value::Array(
+                        row_group_metadata
+                            .columns()
+                            .iter()
+                            .map(column_statistics)
+                            .collect_vec(),
+                    ),
+                );
                 Value::Object(map)
             })
             .collect_vec(),
@@ -141,3 +792,389 @@ pub fn print_row_groups_metadata(input_file: &str) -> Result<(), Box<dyn Error>>
     println!("{}", serde_json::to_string(&row_groups)?);
     Ok(())
 }
+
+fn column_statistics(column_metadata: &ColumnChunkMetaData) -> Value {
+    let statistics = column_metadata.statistics();
+    let (min, max) = match statistics {
+        Some(statistics) if statistics.has_min_max_set() => (
+            statistics_value(statistics, true),
+            statistics_value(statistics, false),
+        ),
+        _ => (Value::Null, Value::Null),
+    };
+
+    let mut map = serde_json::Map::with_capacity(5);
+    map.insert(
+        String::from("name"),
+        Value::String(column_metadata.column_path().string()),
+    );
+    map.insert(String::from("min"), min);
+    map.insert(String::from("max"), max);
+    map.insert(
+        String::from("nullCount"),
+        statistics.map_or(Value::Null, |statistics| {
+            Value::Number(statistics.null_count().into())
+        }),
+    );
+    map.insert(
+        String::from("distinctCount"),
+        statistics
+            .and_then(|statistics| statistics.distinct_count())
+            .map_or(Value::Null, |count| Value::Number(count.into())),
+    );
+    Value::Object(map)
+}
+
+/// Renders either min or max statistics value.
+fn statistics_value(statistics: &Statistics, min: bool) -> Value {
+    match statistics {
+        Statistics::Boolean(s) => Value::Bool(if min { *s.min() } else { *s.max() }),
+        Statistics::Int32(s) => Value::Number(if min { *s.min() } else { *s.max() }.into()),
+        Statistics::Int64(s) => Value::Number(if min { *s.min() } else { *s.max() }.into()),
+        // Sort order of INT96 is undefined, so its min and max are meaningless.
+        Statistics::Int96(_) => Value::Null,
+        Statistics::Float(s) => {
+            float_statistics_value(if min { *s.min() } else { *s.max() }.into())
+        }
+        Statistics::Double(s) => float_statistics_value(if min { *s.min() } else { *s.max() }),
+        Statistics::ByteArray(s) => {
+            bytes_statistics_value(if min { s.min() } else { s.max() }.data())
+        }
+        Statistics::FixedLenByteArray(s) => {
+            bytes_statistics_value(if min { s.min() } else { s.max() }.data())
+        }
+    }
+}
+
+fn float_statistics_value(f: f64) -> Value {
+    Number::from_f64(f).map_or(Value::Null, Value::Number)
+}
+
+/// Strings are rendered as is, other binary values base64 encoded.
+fn bytes_statistics_value(bytes: &[u8]) -> Value {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Value::String(s.to_owned()),
+        Err(_) => Value::String(base64::encode(bytes)),
+    }
+}
+
+/// Prints null and total value counts of every leaf column of a specified Parquet file
+/// as JSON, to find entirely null columns. Null counts are taken from column statistics,
+/// only column chunks without them are scanned (definition levels only).
+///
+/// Values of repeated columns are counted per list element, an empty or null list
+/// counting as a single null.
+///
+/// Arguments:
+///
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
+///
+pub fn print_null_report(input_file: &str) -> Result<(), ConvertError> {
+    let reader = open_reader(input_file)?;
+    let metadata = reader.metadata();
+    let columns = metadata.file_metadata().schema_descr().columns();
+    let mut null_counts = vec![0u64; columns.len()];
+    let mut total_counts = vec![0u64; columns.len()];
+    for (rg_idx, row_group_metadata) in metadata.row_groups().iter().enumerate() {
+        for (i, column_metadata) in row_group_metadata.columns().iter().enumerate() {
+            total_counts[i] += column_metadata.num_values() as u64;
+            null_counts[i] += match column_metadata.statistics() {
+                Some(statistics) => statistics.null_count(),
+                None => {
+                    info!(
+                        "No statistics of column {} in row group {}, scanning",
+                        column_metadata.column_path().string(),
+                        rg_idx
+                    );
+                    let row_group_reader = reader.get_row_group(rg_idx)?;
+                    count_nulls(
+                        row_group_reader.get_column_reader(i)?,
+                        columns[i].max_def_level(),
+                    )?
+                }
+            };
+        }
+    }
+
+    let report = Value::Array(
+        columns
+            .iter()
+            .zip(null_counts.into_iter().zip(total_counts))
+            .map(|(column, (null_count, total_count))| {
+                let mut map = serde_json::Map::with_capacity(4);
+                map.insert(String::from("name"), Value::String(column.path().string()));
+                map.insert(String::from("nullCount"), Value::Number(null_count.into()));
+                map.insert(
+                    String::from("totalCount"),
+                    Value::Number(total_count.into()),
+                );
+                map.insert(
+                    String::from("allNull"),
+                    Value::Bool(null_count == total_count),
+                );
+                Value::Object(map)
+            })
+            .collect_vec(),
+    );
+
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(())
+}
+
+/// Counts null values of a column chunk, i.e. definition levels below the maximum.
+fn count_nulls(column_reader: ColumnReader, max_def_level: i16) -> ParquetResult<u64> {
+    if max_def_level == 0 {
+        return Ok(0);
+    }
+    match column_reader {
+        ColumnReader::BoolColumnReader(r) => count_typed_nulls(r, max_def_level),
+        ColumnReader::Int32ColumnReader(r) => count_typed_nulls(r, max_def_level),
+        ColumnReader::Int64ColumnReader(r) => count_typed_nulls(r, max_def_level),
+        ColumnReader::Int96ColumnReader(r) => count_typed_nulls(r, max_def_level),
+        ColumnReader::FloatColumnReader(r) => count_typed_nulls(r, max_def_level),
+        ColumnReader::DoubleColumnReader(r) => count_typed_nulls(r, max_def_level),
+        ColumnReader::ByteArrayColumnReader(r) => count_typed_nulls(r, max_def_level),
+        ColumnReader::FixedLenByteArrayColumnReader(r) => count_typed_nulls(r, max_def_level),
+    }
+}
+
+fn count_typed_nulls<T: DataType>(
+    mut column_reader: ColumnReaderImpl<T>,
+    max_def_level: i16,
+) -> ParquetResult<u64> {
+    const BATCH_SIZE: usize = 4096;
+    let mut def_levels = vec![0i16; BATCH_SIZE];
+    let mut values = vec![T::T::default(); BATCH_SIZE];
+    let mut null_count = 0;
+    loop {
+        let (_, levels_read) =
+            column_reader.read_batch(BATCH_SIZE, Some(&mut def_levels), None, &mut values)?;
+        if levels_read == 0 {
+            return Ok(null_count);
+        }
+        null_count += def_levels[..levels_read]
+            .iter()
+            .filter(|&&level| level < max_def_level)
+            .count() as u64;
+    }
+}
+
+/// Prints the total number of rows of a specified Parquet file,
+/// as recorded in the row groups metadata (no data pages are read).
+///
+/// Arguments:
+///
+/// * `input_file` - Parquet file path (`-` denotes STDIN)
+///
+pub fn print_row_count(input_file: &str) -> Result<(), ConvertError> {
+    let reader = open_reader(input_file)?;
+    let row_count: i64 = reader
+        .metadata()
+        .row_groups()
+        .iter()
+        .map(|row_group_metadata| row_group_metadata.num_rows())
+        .sum();
+    println!("{}", row_count);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::schema::parser::parse_message_type;
+
+    fn uuid_schema() -> Type {
+        parse_message_type(
+            "message schema {
+                REQUIRED FIXED_LEN_BYTE_ARRAY (16) id;
+                OPTIONAL BYTE_ARRAY ref_id;
+                OPTIONAL INT64 count;
+            }",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn uuid_columns_are_guid() {
+        let schema = uuid_schema();
+        let settings = Settings {
+            uuid_columns: vec![
+                String::from("id"),
+                String::from("ref_id"),
+                String::from("count"),
+            ],
+            ..Settings::default()
+        };
+        let types = schema
+            .get_fields()
+            .iter()
+            .map(|field| overridden_csl_schema(&settings, field))
+            .collect_vec();
+        // Non-binary columns aren't rendered as GUIDs.
+        assert_eq!(
+            types,
+            vec![("id", "guid"), ("ref_id", "guid"), ("count", "long")]
+        );
+    }
+
+    #[test]
+    fn type_override_takes_precedence_over_uuid() {
+        let schema = uuid_schema();
+        let mut settings = Settings {
+            uuid_columns: vec![String::from("id")],
+            ..Settings::default()
+        };
+        settings
+            .type_overrides
+            .insert(String::from("id"), String::from("string"));
+        assert_eq!(
+            overridden_csl_schema(&settings, &schema.get_fields()[0]),
+            ("id", "string")
+        );
+    }
+
+    #[test]
+    fn binary_columns_without_hint_are_dynamic() {
+        let schema = uuid_schema();
+        let settings = Settings::default();
+        assert_eq!(
+            overridden_csl_schema(&settings, &schema.get_fields()[0]),
+            ("id", "dynamic")
+        );
+    }
+
+    #[test]
+    fn legacy_repeated_primitive_is_dynamic() {
+        let schema = parse_message_type(
+            "message schema {
+                REPEATED INT32 r;
+                REQUIRED INT32 id;
+            }",
+        )
+        .unwrap();
+        let types = schema
+            .get_fields()
+            .iter()
+            .map(|field| field_csl_schema(field))
+            .collect_vec();
+        assert_eq!(types, vec![("r", "dynamic"), ("id", "int")]);
+    }
+
+    #[test]
+    fn json_schema_describes_every_field() {
+        let schema = parse_message_type(
+            "message schema {
+                REQUIRED INT64 id;
+                OPTIONAL BYTE_ARRAY name (UTF8);
+                OPTIONAL FIXED_LEN_BYTE_ARRAY (16) amount (DECIMAL(30,4));
+                OPTIONAL group tags (LIST) {
+                    REPEATED group list {
+                        OPTIONAL BYTE_ARRAY element (UTF8);
+                    }
+                }
+            }",
+        )
+        .unwrap();
+        let expected = serde_json::json!({
+            "name": "schema",
+            "type": "group",
+            "fields": [
+                {"name": "id", "repetition": "REQUIRED", "type": "INT64"},
+                {
+                    "name": "name",
+                    "repetition": "OPTIONAL",
+                    "logicalType": "UTF8",
+                    "type": "BYTE_ARRAY"
+                },
+                {
+                    "name": "amount",
+                    "repetition": "OPTIONAL",
+                    "logicalType": "DECIMAL",
+                    "type": "FIXED_LEN_BYTE_ARRAY",
+                    "length": 16,
+                    "precision": 30,
+                    "scale": 4
+                },
+                {
+                    "name": "tags",
+                    "repetition": "OPTIONAL",
+                    "logicalType": "LIST",
+                    "type": "group",
+                    "fields": [{
+                        "name": "list",
+                        "repetition": "REPEATED",
+                        "type": "group",
+                        "fields": [{
+                            "name": "element",
+                            "repetition": "OPTIONAL",
+                            "logicalType": "UTF8",
+                            "type": "BYTE_ARRAY"
+                        }]
+                    }]
+                }
+            ]
+        });
+        let json_schema = field_json_schema(&schema);
+        assert_eq!(json_schema, expected);
+        // Keys are written in a stable order.
+        assert_eq!(
+            serde_json::to_string(&json_schema).unwrap(),
+            serde_json::to_string(&expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn record_schema_of_nested_time_is_integer() {
+        let schema = parse_message_type(
+            "message schema {
+                REQUIRED INT32 t (TIME_MILLIS);
+                REQUIRED group nested {
+                    REQUIRED INT32 t (TIME_MILLIS);
+                    REQUIRED INT64 t_us (TIME_MICROS);
+                }
+            }",
+        )
+        .unwrap();
+        let record = Value::Object(record_schema(&Settings::default(), schema.get_fields()));
+        assert_eq!(
+            record["properties"]["t"],
+            serde_json::json!({"type": "string", "format": "time"})
+        );
+        let nested = &record["properties"]["nested"]["properties"];
+        assert_eq!(nested["t"], serde_json::json!({"type": "integer"}));
+        assert_eq!(nested["t_us"], serde_json::json!({"type": "integer"}));
+    }
+
+    #[test]
+    fn record_schema_has_aliases_and_synthetic_columns() {
+        let schema = parse_message_type(
+            "message schema {
+                REQUIRED INT32 id;
+                OPTIONAL BYTE_ARRAY name (UTF8);
+            }",
+        )
+        .unwrap();
+        let mut settings = Settings {
+            rownum_column: Some(String::from("rownum")),
+            filename_column: Some(String::from("file")),
+            ..Settings::default()
+        };
+        settings
+            .column_aliases
+            .insert(String::from("id"), String::from("key"));
+        let record = Value::Object(record_schema(&settings, schema.get_fields()));
+        assert_eq!(
+            record,
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "key": {"type": "integer"},
+                    "name": {"type": ["string", "null"]},
+                    "rownum": {"type": "integer"},
+                    "file": {"type": "string"}
+                },
+                "required": ["key", "rownum", "file"]
+            })
+        );
+    }
+}
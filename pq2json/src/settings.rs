@@ -1,12 +1,175 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use chrono_tz::Tz;
+use regex::Regex;
+
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub omit_nulls: bool,
+    pub keep_nulls: Vec<String>,
     pub omit_empty_bags: bool,
+    pub skip_empty_rows: bool,
     pub omit_empty_lists: bool,
+    pub empty_string_as_null: bool,
     pub convert_types: bool,
+    pub on_overflow: OverflowHandling,
+    pub float_special: FloatSpecial,
+    pub decimal_as_number: bool,
+    pub long_as_string: bool,
+    pub lenient: bool,
+    pub max_depth: usize,
+    pub on_duplicate_key: DuplicateKey,
     pub timestamp_rendering: TimestampRendering,
+    pub ticks_as_string: bool,
+    pub pre_epoch: PreEpoch,
+    pub timezone: Option<Tz>,
+    pub timestamp_format: Option<String>,
+    pub time_as_number: bool,
     pub columns: Option<Vec<String>>,
-    pub csv: bool,
+    pub columns_regex: Option<Regex>,
+    pub column_aliases: HashMap<String, String>,
+    pub type_overrides: HashMap<String, String>,
+    pub transforms: HashMap<String, Transform>,
+    pub exclude_columns: Option<Vec<String>>,
+    pub filename_column: Option<String>,
+    pub rownum_column: Option<String>,
+    pub rownum_base: usize,
+    pub strict_columns: bool,
+    pub ignore_schema_mismatch: bool,
+    pub flatten: bool,
+    pub flatten_separator: String,
+    pub explode: Option<String>,
+    pub explode_empty: ExplodeEmpty,
+    pub format: OutputFormat,
+    pub mapping_out: Option<String>,
+    pub newline: Newline,
+    pub csv_terminator: CsvTerminator,
+    pub csv_quote: CsvQuote,
+    pub output_encoding: OutputEncoding,
+    pub csv_delimiter: u8,
+    pub csv_header: bool,
+    pub csv_null: String,
+    pub bool_as_int: bool,
+    pub bytes_encoding: BytesEncoding,
+    pub bytes_as_string: bool,
+    pub uuid_columns: Vec<String>,
+    pub gzip: bool,
+    pub gzip_level: u32,
+    pub append: bool,
+    pub checksum: bool,
+    pub checksum_out: Option<String>,
+    pub buffer_size: Option<usize>,
+    pub split_rows: Option<usize>,
+    pub split_bytes: Option<u64>,
+    pub per_row_group: bool,
+    pub limit: Option<usize>,
+    pub skip: usize,
+    pub resume_from: usize,
+    pub tail: Option<usize>,
+    pub row_groups: Option<Vec<usize>>,
+    pub filter: Option<RowFilter>,
+    pub sample: Option<f64>,
+    pub seed: Option<u64>,
+    pub threads: usize,
+    pub mmap: bool,
+    pub progress: bool,
+    pub pretty: bool,
+}
+
+/// Defaults of the command line options.
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            omit_nulls: false,
+            keep_nulls: Vec::new(),
+            omit_empty_bags: false,
+            skip_empty_rows: false,
+            omit_empty_lists: false,
+            empty_string_as_null: false,
+            convert_types: false,
+            on_overflow: OverflowHandling::Keep,
+            float_special: FloatSpecial::Null,
+            decimal_as_number: false,
+            long_as_string: false,
+            lenient: false,
+            max_depth: 100,
+            on_duplicate_key: DuplicateKey::Last,
+            timestamp_rendering: TimestampRendering::Ticks,
+            ticks_as_string: false,
+            pre_epoch: PreEpoch::Keep,
+            timezone: None,
+            timestamp_format: None,
+            time_as_number: false,
+            columns: None,
+            columns_regex: None,
+            column_aliases: HashMap::new(),
+            type_overrides: HashMap::new(),
+            transforms: HashMap::new(),
+            exclude_columns: None,
+            filename_column: None,
+            rownum_column: None,
+            rownum_base: 0,
+            strict_columns: false,
+            ignore_schema_mismatch: false,
+            flatten: false,
+            flatten_separator: String::from("."),
+            explode: None,
+            explode_empty: ExplodeEmpty::Drop,
+            format: OutputFormat::Jsonl,
+            mapping_out: None,
+            newline: Newline::Lf,
+            csv_terminator: CsvTerminator::Lf,
+            csv_quote: CsvQuote::Necessary,
+            output_encoding: OutputEncoding::Utf8,
+            csv_delimiter: b',',
+            csv_header: false,
+            csv_null: String::new(),
+            bool_as_int: false,
+            bytes_encoding: BytesEncoding::Array,
+            bytes_as_string: false,
+            uuid_columns: Vec::new(),
+            gzip: false,
+            gzip_level: 6,
+            append: false,
+            checksum: false,
+            checksum_out: None,
+            buffer_size: None,
+            split_rows: None,
+            split_bytes: None,
+            per_row_group: false,
+            limit: None,
+            skip: 0,
+            resume_from: 0,
+            tail: None,
+            row_groups: None,
+            filter: None,
+            sample: None,
+            seed: None,
+            threads: 1,
+            mmap: false,
+            progress: false,
+            pretty: false,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// Line delimited JSON.
+    Jsonl,
+    /// Root level fields as CSV, nested structures formatted as JSON strings.
+    Csv,
+    /// Same as CSV, with tab delimiter. Tabs, line breaks and backslashes in fields are
+    /// escaped (`\t`, `\n`, `\r`, `\\`) rather than quoted, as Kusto TSV ingestion expects.
+    Tsv,
+    /// Single JSON array of records.
+    JsonArray,
+    /// Arrow IPC stream.
+    Arrow,
+    /// JSON records for Kusto `multijson` ingestion, one after another (pretty-printed
+    /// with --pretty).
+    KustoMultiJson,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -15,3 +178,274 @@ pub enum TimestampRendering {
     IsoStr,
     UnixMs,
 }
+
+/// Handling of timestamps before the Unix epoch.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PreEpoch {
+    /// Render as the epoch.
+    Clamp,
+    Null,
+    /// Render the actual pre-1970 timestamp.
+    Keep,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BytesEncoding {
+    Array,
+    Base64,
+    Hex,
+}
+
+/// Line terminator of JSON output.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Newline {
+    Lf,
+    Crlf,
+}
+
+impl Newline {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::Crlf => "\r\n",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CsvTerminator {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+/// Quoting of CSV fields (TSV fields are escaped instead).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CsvQuote {
+    Always,
+    /// Only fields containing the delimiter, quotes or line breaks.
+    Necessary,
+    /// All fields except numbers.
+    NonNumeric,
+    Never,
+}
+
+/// Character encoding of CSV and TSV output, JSON output is always UTF-8.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OutputEncoding {
+    Utf8,
+    /// UTF-16 little endian, starting with a byte order mark.
+    Utf16Le,
+}
+
+/// Handling of unsigned values exceeding the signed Kusto type under --convert-types.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OverflowHandling {
+    /// Keep the value as an unsigned JSON number.
+    Keep,
+    /// Render the value as a JSON string.
+    String,
+    /// Fail the conversion.
+    Error,
+}
+
+/// Rendering of NaN and infinite floating point values.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FloatSpecial {
+    Null,
+    /// "NaN", "Infinity" or "-Infinity" strings.
+    String,
+}
+
+/// Output of --explode for rows whose list is empty or null.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ExplodeEmpty {
+    /// Drop the row.
+    Drop,
+    /// Emit the row once with a null element.
+    Null,
+}
+
+/// Handling of map entries whose key (as rendered) repeats within a map.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DuplicateKey {
+    /// Keep the last value.
+    Last,
+    /// Keep the first value.
+    First,
+    /// Fail the conversion.
+    Error,
+    /// Collect all values of the key into a JSON array.
+    Array,
+}
+
+/// Function applied to the values of a top-level column (see --transform).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Transform {
+    Upper,
+    Lower,
+    /// Strip leading and trailing whitespace.
+    Trim,
+    /// Round floating point numbers to the given number of decimal places.
+    Round(u32),
+}
+
+impl Transform {
+    /// Parses transform expression such as `name=upper(name)`, `price=round(price, 2)`,
+    /// returning the column and its transform.
+    pub fn parse(expr: &str) -> Result<(String, Transform), String> {
+        let invalid = || {
+            format!(
+                "Expected transform of the form column=function(column), got '{}'",
+                expr
+            )
+        };
+        let pos = expr.find('=').ok_or_else(invalid)?;
+        let column = expr[..pos].trim();
+        let call = expr[pos + 1..].trim();
+        let open = call.find('(').ok_or_else(invalid)?;
+        if column.is_empty() || !call.ends_with(')') {
+            return Err(invalid());
+        }
+        let function = call[..open].trim();
+        let args = call[open + 1..call.len() - 1]
+            .split(',')
+            .map(|arg| arg.trim())
+            .collect::<Vec<_>>();
+        if args[0] != column {
+            return Err(format!(
+                "Transform of column {} must take the column itself as its argument, got '{}'",
+                column, expr
+            ));
+        }
+        let transform = match (function, &args[1..]) {
+            ("upper", []) => Transform::Upper,
+            ("lower", []) => Transform::Lower,
+            ("trim", []) => Transform::Trim,
+            ("round", []) => Transform::Round(0),
+            ("round", [digits]) => Transform::Round(digits.parse::<u32>().map_err(|_| {
+                format!(
+                    "Expected round() decimal places to be a non-negative integer, got '{}'",
+                    digits
+                )
+            })?),
+            ("upper", _) | ("lower", _) | ("trim", _) | ("round", _) => {
+                return Err(format!(
+                    "Too many arguments of {}() in '{}'",
+                    function, expr
+                ))
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown transform function '{}', expected one of: upper, lower, trim, round",
+                    function
+                ))
+            }
+        };
+        Ok((column.to_owned(), transform))
+    }
+}
+
+/// Row filter comparing a top-level scalar column with a constant (`column<op>value`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowFilter {
+    pub column: String,
+    pub op: FilterOp,
+    pub value: String,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+impl FilterOp {
+    pub fn matches(self, ordering: Ordering) -> bool {
+        match self {
+            FilterOp::Eq => ordering == Ordering::Equal,
+            FilterOp::Ne => ordering != Ordering::Equal,
+            FilterOp::Lt => ordering == Ordering::Less,
+            FilterOp::Gt => ordering == Ordering::Greater,
+            FilterOp::Le => ordering != Ordering::Greater,
+            FilterOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl RowFilter {
+    /// Parses filter expression such as `name==value`, `size>10`, `size<=10`.
+    pub fn parse(expr: &str) -> Result<RowFilter, String> {
+        // Two-character operators go first, so that `>=` is not taken for `>` and `=5`.
+        let ops = [
+            ("==", FilterOp::Eq),
+            ("!=", FilterOp::Ne),
+            ("<=", FilterOp::Le),
+            (">=", FilterOp::Ge),
+            ("<", FilterOp::Lt),
+            (">", FilterOp::Gt),
+        ];
+        for (token, op) in ops.iter() {
+            if let Some(pos) = expr.find(token) {
+                let column = expr[..pos].trim();
+                if column.is_empty() {
+                    return Err(format!("Missing column name in filter '{}'", expr));
+                }
+                return Ok(RowFilter {
+                    column: column.to_owned(),
+                    op: *op,
+                    value: expr[pos + token.len()..].trim().to_owned(),
+                });
+            }
+        }
+        Err(format!(
+            "Expected filter of the form column==value (or !=, <, >, <=, >=), got '{}'",
+            expr
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(column: &str, op: FilterOp, value: &str) -> RowFilter {
+        RowFilter {
+            column: column.to_owned(),
+            op,
+            value: value.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parse_filter_operators() {
+        assert_eq!(RowFilter::parse("a==1"), Ok(filter("a", FilterOp::Eq, "1")));
+        assert_eq!(
+            RowFilter::parse("a != x"),
+            Ok(filter("a", FilterOp::Ne, "x"))
+        );
+        assert_eq!(RowFilter::parse("a<1"), Ok(filter("a", FilterOp::Lt, "1")));
+        assert_eq!(RowFilter::parse("a>1"), Ok(filter("a", FilterOp::Gt, "1")));
+        assert_eq!(RowFilter::parse("a<=1"), Ok(filter("a", FilterOp::Le, "1")));
+        assert_eq!(RowFilter::parse("x>=5"), Ok(filter("x", FilterOp::Ge, "5")));
+    }
+
+    #[test]
+    fn parse_invalid_filter() {
+        assert!(RowFilter::parse("==1").is_err());
+        assert!(RowFilter::parse("a").is_err());
+    }
+
+    #[test]
+    fn inclusive_operators_match_equal() {
+        assert!(FilterOp::Ge.matches(Ordering::Equal));
+        assert!(FilterOp::Ge.matches(Ordering::Greater));
+        assert!(!FilterOp::Ge.matches(Ordering::Less));
+        assert!(FilterOp::Le.matches(Ordering::Equal));
+        assert!(!FilterOp::Le.matches(Ordering::Greater));
+    }
+}